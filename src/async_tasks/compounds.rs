@@ -0,0 +1,161 @@
+//! Dictionary based compound word decomposition
+//!
+//! German compounds like "Datenschutzerklärung" are split into parts found in
+//! the keyword vocabulary of the database, so each part can be searched on its own.
+
+use std::collections::HashSet;
+
+/// Linking elements ("Fugenelemente") that may follow a part inside a compound
+const LINKING_ELEMENTS: [&str; 5] = ["es", "en", "s", "n", "e"];
+
+#[derive(Debug)]
+pub struct CompoundSplitter {
+    vocabulary: HashSet<String>,
+    min_part_len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Segmentation {
+    linking_chars: usize,
+    parts: Vec<String>,
+}
+
+impl Segmentation {
+    /// Prefer attributing characters to parts rather than to linking elements,
+    /// then fewer parts
+    fn cost(&self) -> (usize, usize) {
+        (self.linking_chars, self.parts.len())
+    }
+}
+
+impl CompoundSplitter {
+    pub fn new<'a>(words: impl Iterator<Item = &'a str>, min_part_len: usize) -> Self {
+        let mut vocabulary = HashSet::new();
+        for word in words {
+            let word = word.to_lowercase();
+            let parts = word
+                .split(|c: char| c.is_whitespace() || c == '-')
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>();
+            if parts.len() > 1 {
+                vocabulary.insert(parts.concat());
+            }
+            parts
+                .into_iter()
+                .filter(|part| part.chars().count() >= min_part_len)
+                .for_each(|part| {
+                    vocabulary.insert(part.to_string());
+                });
+        }
+        Self {
+            vocabulary,
+            min_part_len,
+        }
+    }
+
+    /// Split a compound into its parts. Every part must be a word of the vocabulary,
+    /// optionally followed by a linking element.
+    /// Returns None if the word is no compound of the vocabulary. Only single alphabetic
+    /// words are split, phrases like "privacy policy" and codes like "tls 1.3" are not.
+    pub fn split(&self, word: &str) -> Option<Vec<String>> {
+        if !word.chars().all(char::is_alphabetic) {
+            return None;
+        }
+        let chars = word.to_lowercase().chars().collect::<Vec<_>>();
+        if chars.len() < 2 * self.min_part_len {
+            return None;
+        }
+
+        let mut best: Vec<Option<Segmentation>> = vec![None; chars.len() + 1];
+        best[0] = Some(Segmentation {
+            linking_chars: 0,
+            parts: vec![],
+        });
+        for end in self.min_part_len..=chars.len() {
+            for start in 0..=end - self.min_part_len {
+                let Some(prefix) = &best[start] else {
+                    continue;
+                };
+                let segment = chars[start..end].iter().collect::<String>();
+                let Some(part) = self.known_part(&segment) else {
+                    continue;
+                };
+                let candidate = Segmentation {
+                    linking_chars: prefix.linking_chars + (end - start) - part.chars().count(),
+                    parts: [prefix.parts.as_slice(), &[part]].concat(),
+                };
+                if best[end]
+                    .as_ref()
+                    .is_none_or(|current| candidate.cost() < current.cost())
+                {
+                    best[end] = Some(candidate);
+                }
+            }
+        }
+
+        best[chars.len()]
+            .take()
+            .and_then(|segmentation| (segmentation.parts.len() > 1).then_some(segmentation.parts))
+    }
+
+    fn known_part(&self, segment: &str) -> Option<String> {
+        if self.vocabulary.contains(segment) {
+            return Some(segment.to_string());
+        }
+        LINKING_ELEMENTS.iter().find_map(|linking| {
+            segment
+                .strip_suffix(linking)
+                .filter(|stem| {
+                    stem.chars().count() >= self.min_part_len && self.vocabulary.contains(*stem)
+                })
+                .map(|stem| stem.to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splitter() -> CompoundSplitter {
+        let words = [
+            "datenschutz",
+            "erklärung",
+            "arbeit",
+            "zeit",
+            "data",
+            "privacy",
+            "policy",
+            "tls 1.3",
+        ];
+        CompoundSplitter::new(words.into_iter(), 3)
+    }
+
+    #[test]
+    fn splits_compounds_of_the_vocabulary() {
+        assert_eq!(
+            splitter().split("Datenschutzerklärung"),
+            Some(vec!["datenschutz".to_string(), "erklärung".to_string()])
+        );
+        // linking element "s"
+        assert_eq!(
+            splitter().split("arbeitszeit"),
+            Some(vec!["arbeit".to_string(), "zeit".to_string()])
+        );
+    }
+
+    #[test]
+    fn keeps_phrases_and_numbers() {
+        assert_eq!(splitter().split("tls 1.3"), None);
+        assert_eq!(splitter().split("privacy policy"), None);
+        assert_eq!(splitter().split("privacy-policy"), None);
+        assert_eq!(splitter().split("oauth2"), None);
+    }
+
+    #[test]
+    fn rejects_unknown_parts() {
+        assert_eq!(splitter().split("database"), None);
+        assert_eq!(splitter().split("dataset"), None);
+        assert_eq!(splitter().split("datenschutz"), None);
+    }
+}
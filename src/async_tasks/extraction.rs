@@ -0,0 +1,174 @@
+//! Keyword extraction from natural language questions
+//!
+//! Questions are tokenized like keyword strings. Word n-grams found in the keyword
+//! vocabulary of the database are taken as keywords, longest first, so
+//! "how long is my chat history kept" yields "chat history" rather than "chat" and "history".
+//! The remaining words are candidates unless they are stop words.
+
+use super::tokenizer::split_keywords;
+use std::collections::HashSet;
+
+/// Longest vocabulary phrase in words that is matched in a question
+const MAX_NGRAM: usize = 4;
+
+const STOP_WORDS_EN: &[&str] = &[
+    "a", "about", "all", "am", "an", "and", "any", "are", "as", "at", "be", "been", "but", "by",
+    "can", "could", "did", "do", "does", "for", "from", "get", "has", "have", "how", "i", "if",
+    "in", "is", "it", "its", "me", "my", "need", "of", "on", "or", "our", "please", "should",
+    "so", "some", "tell", "than", "that", "the", "their", "them", "then", "there", "these",
+    "they", "this", "those", "to", "us", "use", "was", "we", "were", "what", "what's", "when",
+    "where", "which", "who", "why", "will", "with", "would", "you", "your", "yours", "know",
+    "there's", "it's", "want", "much",
+];
+
+const STOP_WORDS_DE: &[&str] = &[
+    "aber", "alle", "als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "da", "das",
+    "dass", "dem", "den", "der", "des", "die", "du", "ein", "eine", "einem", "einen", "einer",
+    "es", "für", "gibt", "habe", "haben", "hat", "ich", "ihr", "im", "in", "ist", "kann",
+    "können", "mein", "meine", "mich", "mir", "mit", "nach", "nicht", "noch", "oder", "sie",
+    "sind", "so", "über", "um", "und", "uns", "unser", "von", "vor", "was", "welche", "wenn",
+    "werden", "wie", "wir", "wird", "wo", "zu",
+];
+
+/// Keyword extracted from a question
+#[derive(Debug, Clone)]
+pub struct ExtractedKeyword {
+    pub keyword: String,
+    /// true if the keyword is a phrase of the vocabulary, otherwise a remaining word
+    pub known: bool,
+}
+
+#[derive(Debug)]
+pub struct KeywordExtractor {
+    vocabulary: HashSet<String>,
+    max_ngram: usize,
+}
+
+impl KeywordExtractor {
+    pub fn new<'a>(words: impl Iterator<Item = &'a str>) -> Self {
+        let vocabulary = words
+            .map(|word| word.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<HashSet<_>>();
+        let max_ngram = vocabulary
+            .iter()
+            .map(|word| word.split(' ').count())
+            .max()
+            .unwrap_or(1)
+            .min(MAX_NGRAM);
+        Self {
+            vocabulary,
+            max_ngram,
+        }
+    }
+
+    /// Keywords of a question in question order, without duplicates
+    pub fn extract(&self, question: &str) -> Vec<ExtractedKeyword> {
+        let tokens = split_keywords(question)
+            .into_iter()
+            .map(|token| token.to_lowercase())
+            .collect::<Vec<_>>();
+        let mut keywords: Vec<ExtractedKeyword> = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let phrase = (1..=self.max_ngram.min(tokens.len() - i))
+                .rev()
+                .map(|n| (n, tokens[i..i + n].join(" ")))
+                .find(|(_n, phrase)| self.vocabulary.contains(phrase));
+            let (n, keyword) = match phrase {
+                Some((n, phrase)) => (
+                    n,
+                    ExtractedKeyword {
+                        keyword: phrase,
+                        known: true,
+                    },
+                ),
+                None if is_candidate(&tokens[i]) => (
+                    1,
+                    ExtractedKeyword {
+                        keyword: tokens[i].clone(),
+                        known: false,
+                    },
+                ),
+                None => {
+                    i += 1;
+                    continue;
+                }
+            };
+            if !keywords.iter().any(|k| k.keyword == keyword.keyword) {
+                keywords.push(keyword);
+            }
+            i += n;
+        }
+        keywords
+    }
+}
+
+/// Lowercase words of a text without stop words and words shorter than three characters
+pub fn significant_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| word.chars().count() >= 3 && is_candidate(word))
+        .collect()
+}
+
+fn is_candidate(word: &str) -> bool {
+    word.chars().count() > 1
+        && word.chars().any(char::is_alphanumeric)
+        && !STOP_WORDS_EN.contains(&word)
+        && !STOP_WORDS_DE.contains(&word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extractor() -> KeywordExtractor {
+        let vocabulary = ["security", "chat history", "tls 1.3", "gdpr", "data residency"];
+        KeywordExtractor::new(vocabulary.into_iter())
+    }
+
+    fn keywords(question: &str) -> Vec<(String, bool)> {
+        extractor()
+            .extract(question)
+            .into_iter()
+            .map(|extracted| (extracted.keyword, extracted.known))
+            .collect()
+    }
+
+    #[test]
+    fn prefers_longest_vocabulary_phrases() {
+        assert_eq!(
+            keywords("How long is my chat history kept?"),
+            vec![
+                ("long".to_string(), false),
+                ("chat history".to_string(), true),
+                ("kept".to_string(), false)
+            ]
+        );
+        assert_eq!(
+            keywords("Do you support TLS 1.3 and GDPR?"),
+            vec![
+                ("support".to_string(), false),
+                ("tls 1.3".to_string(), true),
+                ("gdpr".to_string(), true)
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_stop_words_and_duplicates() {
+        assert_eq!(
+            keywords("Was ist die Security, security?"),
+            vec![("security".to_string(), true)]
+        );
+        assert!(keywords("what is it?").is_empty());
+    }
+
+    #[test]
+    fn significant_words_of_a_message() {
+        assert_eq!(
+            significant_words("We are in Europe, how do we delete our data?"),
+            HashSet::from(["europe", "delete", "data"].map(String::from))
+        );
+    }
+}
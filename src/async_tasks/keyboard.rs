@@ -0,0 +1,108 @@
+//! Keyboard adjacency aware typo similarity
+//!
+//! Substituting a character by a neighbouring key on the configured layout
+//! ("secirity", "gdor") is considered a cheaper edit than an arbitrary
+//! substitution.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Cost of substituting a character with a key adjacent on the keyboard
+const ADJACENT_SUBSTITUTION_COST: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyboardLayout {
+    Qwerty,
+    Qwertz,
+}
+
+impl KeyboardLayout {
+    fn rows(&self) -> [&'static str; 4] {
+        match self {
+            KeyboardLayout::Qwerty => ["1234567890-", "qwertyuiop[", "asdfghjkl;'", "zxcvbnm,./"],
+            KeyboardLayout::Qwertz => ["1234567890ß", "qwertzuiopü", "asdfghjklöä", "yxcvbnm,.-"],
+        }
+    }
+
+    fn position(&self, ch: char) -> Option<(usize, usize)> {
+        self.rows().iter().enumerate().find_map(|(row, keys)| {
+            keys.chars()
+                .position(|key| key == ch)
+                .map(|col| (row, col))
+        })
+    }
+
+    /// true if both characters are on neighbouring keys, taking the row stagger into account
+    pub fn adjacent(&self, a: char, b: char) -> bool {
+        match (self.position(a), self.position(b)) {
+            (Some((row_a, col_a)), Some((row_b, col_b))) => {
+                if row_a == row_b {
+                    col_a.abs_diff(col_b) == 1
+                } else if row_b + 1 == row_a {
+                    col_b == col_a || col_b == col_a + 1
+                } else if row_a + 1 == row_b {
+                    col_b == col_a || col_b + 1 == col_a
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Similarity in [0, 1] based on a weighted levenshtein distance where
+/// substitutions between adjacent keys are cheaper than other edits.
+pub fn typo_similarity(a: &str, b: &str, layout: KeyboardLayout) -> f64 {
+    let a = a.to_lowercase().chars().collect::<Vec<_>>();
+    let b = b.to_lowercase().chars().collect::<Vec<_>>();
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+
+    let mut prev = (0..=b.len()).map(|j| j as f64).collect::<Vec<_>>();
+    let mut curr = vec![0.0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = (i + 1) as f64;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = if ca == cb {
+                0.0
+            } else if layout.adjacent(*ca, *cb) {
+                ADJACENT_SUBSTITUTION_COST
+            } else {
+                1.0
+            };
+            curr[j + 1] = (prev[j] + substitution)
+                .min(prev[j + 1] + 1.0)
+                .min(curr[j] + 1.0);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    1.0 - prev[b.len()] / max_len as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjacent_keys_depend_on_layout() {
+        assert!(KeyboardLayout::Qwerty.adjacent('s', 'd'));
+        assert!(KeyboardLayout::Qwerty.adjacent('u', 'i'));
+        assert!(!KeyboardLayout::Qwerty.adjacent('s', 'k'));
+        assert!(!KeyboardLayout::Qwerty.adjacent('t', 'z'));
+        assert!(KeyboardLayout::Qwertz.adjacent('t', 'z'));
+    }
+
+    #[test]
+    fn adjacent_substitution_is_cheaper() {
+        let layout = KeyboardLayout::Qwerty;
+        assert_eq!(typo_similarity("Security", "security", layout), 1.0);
+        assert_eq!(typo_similarity("secirity", "security", layout), 1.0 - 0.5 / 8.0);
+        assert_eq!(typo_similarity("secprity", "security", layout), 1.0 - 1.0 / 8.0);
+        assert_eq!(typo_similarity("", "", layout), 1.0);
+    }
+}
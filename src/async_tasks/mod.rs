@@ -8,11 +8,22 @@ use anyhow::anyhow;
 
 mod trigrams;
 pub use trigrams::*;
+mod keyboard;
+pub use keyboard::KeyboardLayout;
+mod compounds;
+pub use compounds::CompoundSplitter;
+mod extraction;
+pub use extraction::{significant_words, ExtractedKeyword, KeywordExtractor};
+mod transliterate;
+mod tokenizer;
+pub use tokenizer::{split_keywords, NumericMatch};
+mod spelling;
+pub use spelling::SpellingDictionary;
 mod morsels;
 // use directory::init_directory;
 use crate::async_tasks::morsels::{init_failed_keywords, init_morsels};
 use log::{debug, error, info};
-pub use morsels::MorselEntry;
+pub use morsels::{MorselEntry, MorselSimilarity};
 use std::sync::mpsc;
 use std::time::Duration;
 
@@ -1,10 +1,18 @@
-use crate::{FailLogEntry, PluginConfig, FAILED_KEYWORDS, MORSEL_TRIGRAMS};
+use crate::{
+    FailLogEntry, PluginConfig, DATABASE_VERSION, FAILED_KEYWORDS, MORSEL_COMPOUNDS,
+    MORSEL_CO_LISTINGS, MORSEL_EXTRACTOR, MORSEL_SPELLING, MORSEL_TRIGRAMS,
+};
 use anyhow::anyhow;
 use log::{debug, error, warn};
-use super::trigrams::{Named, Trigrams};
+use super::compounds::CompoundSplitter;
+use super::extraction::KeywordExtractor;
+use super::spelling::SpellingDictionary;
+use super::trigrams::{Named, Trigrams, TypoScoring};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
+use std::sync::atomic::Ordering;
 use std::io::BufReader;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
@@ -12,17 +20,159 @@ use tokio::io::AsyncReadExt;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MorselEntry {
     pub id: String,
+    /// human readable name of the topic for 'list_topics'
+    pub title: Option<String>,
+    /// category for filtering 'list_topics', e.g. "security" or "billing"
+    pub category: Option<String>,
     pub keywords: Vec<String>,
     pub content: String,
     pub link: Option<String>,
+    /// ids of morsels offered as related topics
+    #[serde(default)]
+    pub related: Vec<String>,
+    /// ids of morsels this one builds on, e.g. its prerequisites
+    #[serde(default)]
+    pub see_also: Vec<String>,
 }
 
 impl Named for MorselEntry {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
     fn names(&self) -> &[String] {
         self.keywords.as_slice()
     }
 }
 
+/// How the similarity between two morsels is measured for result diversity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MorselSimilarity {
+    /// overlap of the keyword sets
+    Keywords,
+    /// overlap of the content vocabulary
+    Content,
+}
+
+impl MorselEntry {
+    /// Jaccard similarity of two morsels in [0, 1]
+    pub fn similarity(&self, other: &MorselEntry, similarity: MorselSimilarity) -> f64 {
+        let (a, b) = match similarity {
+            MorselSimilarity::Keywords => (self.keyword_set(), other.keyword_set()),
+            MorselSimilarity::Content => (self.content_words(), other.content_words()),
+        };
+        let union = a.union(&b).count();
+        if union == 0 {
+            0.0
+        } else {
+            a.intersection(&b).count() as f64 / union as f64
+        }
+    }
+
+    /// Share of the context words found in the keywords or content of the morsel, in [0, 1].
+    /// Words with a common prefix of five characters match, so "delete" finds "deletion".
+    pub fn context_overlap(&self, context_words: &HashSet<String>) -> f64 {
+        if context_words.is_empty() {
+            return 0.0;
+        }
+        let words = self
+            .keywords
+            .iter()
+            .flat_map(|keyword| keyword.split(|c: char| !c.is_alphanumeric()))
+            .map(|word| word.to_lowercase())
+            .chain(self.content_words())
+            .collect::<HashSet<_>>();
+        let matched = context_words
+            .iter()
+            .filter(|context_word| {
+                words.contains(*context_word)
+                    || words.iter().any(|word| common_prefix(word, context_word) >= 5)
+            })
+            .count();
+        matched as f64 / context_words.len() as f64
+    }
+
+    /// Number of keywords the morsels have in common, ignoring case
+    pub fn shared_keywords(&self, other: &MorselEntry) -> usize {
+        self.keyword_set().intersection(&other.keyword_set()).count()
+    }
+
+    fn keyword_set(&self) -> HashSet<String> {
+        self.keywords.iter().map(|k| k.to_lowercase()).collect()
+    }
+
+    fn content_words(&self) -> HashSet<String> {
+        self.content
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() >= 3)
+            .map(|word| word.to_lowercase())
+            .collect()
+    }
+}
+
+/// Report links to unknown morsels and cycles of see also links
+fn validate_links(entries: &[MorselEntry]) {
+    let ids = entries
+        .iter()
+        .map(|entry| entry.id.as_str())
+        .collect::<HashSet<_>>();
+    for entry in entries {
+        for related in &entry.related {
+            if !ids.contains(related.as_str()) {
+                warn!("init_directory: related morsel '{related}' of '{}' not found", entry.id);
+            }
+        }
+        for see_also in &entry.see_also {
+            if !ids.contains(see_also.as_str()) {
+                warn!("init_directory: see also morsel '{see_also}' of '{}' not found", entry.id);
+            }
+        }
+    }
+
+    let see_also = entries
+        .iter()
+        .map(|entry| (entry.id.as_str(), entry.see_also.as_slice()))
+        .collect::<HashMap<_, _>>();
+    let mut done = HashSet::new();
+    let mut cycles = Vec::new();
+    for entry in entries {
+        let mut path = Vec::new();
+        find_cycles(&entry.id, &see_also, &mut path, &mut done, &mut cycles);
+    }
+    for cycle in cycles {
+        warn!("init_directory: see also cycle {}", cycle.join(" -> "));
+    }
+}
+
+/// Depth first search collecting every see also link that closes a cycle,
+/// as the path from the start of the cycle back to it
+fn find_cycles<'a>(
+    id: &'a str,
+    see_also: &HashMap<&'a str, &'a [String]>,
+    path: &mut Vec<&'a str>,
+    done: &mut HashSet<&'a str>,
+    cycles: &mut Vec<Vec<&'a str>>,
+) {
+    if done.contains(id) {
+        return;
+    }
+    if let Some(start) = path.iter().position(|on_path| *on_path == id) {
+        cycles.push([&path[start..], &[id]].concat());
+        return;
+    }
+    path.push(id);
+    for next in see_also.get(id).copied().unwrap_or_default() {
+        find_cycles(next, see_also, path, done, cycles);
+    }
+    path.pop();
+    done.insert(id);
+}
+
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.chars().zip(b.chars()).take_while(|(x, y)| x == y).count()
+}
+
 pub async fn init_morsels(config: &PluginConfig) -> anyhow::Result<()> {
     debug!("init_directory: config: {config:?}");
     let db_path = config.database_path.as_path();
@@ -34,12 +184,49 @@ pub async fn init_morsels(config: &PluginConfig) -> anyhow::Result<()> {
         debug!("init_directory: bytes read:   {bytes_read}");
         let entries: Vec<MorselEntry> = serde_yaml::from_str(buffer.as_str())?;
         debug!("init_directory: parsed {} entries", entries.len());
-        let trigrams = Trigrams::new(entries)?;
+        if let Some(fallback_id) = &config.fallback_morsel_id
+            && !entries.iter().any(|entry| &entry.id == fallback_id)
+        {
+            warn!("init_directory: fallback morsel '{fallback_id}' not found in database");
+        }
+        validate_links(&entries);
+        let spelling = config
+            .spelling_correction
+            .unwrap()
+            .then(|| SpellingDictionary::new(&entries, config.spelling_max_distance.unwrap()));
+        *MORSEL_SPELLING
+            .write()
+            .map_err(|e| anyhow!(e.to_string()))? = spelling;
+        let typo_scoring = config.typo_layout.map(|layout| TypoScoring {
+            layout,
+            weight: config.typo_weight.unwrap(),
+        });
+        let trigrams = Trigrams::new(entries)?
+            .with_typo_scoring(typo_scoring)
+            .with_transliteration(config.transliteration.unwrap())
+            .with_numeric_match(config.numeric_match.unwrap());
         debug!("init_directory: trigrams");
+        let compounds = CompoundSplitter::new(
+            trigrams.vocabulary(),
+            config.compound_min_part_len.unwrap(),
+        );
+        *MORSEL_COMPOUNDS
+            .write()
+            .map_err(|e| anyhow!(e.to_string()))? = Some(compounds);
+        let extractor = KeywordExtractor::new(trigrams.vocabulary());
+        *MORSEL_EXTRACTOR
+            .write()
+            .map_err(|e| anyhow!(e.to_string()))? = Some(extractor);
         let mut tgms = MORSEL_TRIGRAMS
             .write()
             .map_err(|e| anyhow!(e.to_string()))?;
         *tgms = Some(trigrams);
+        // ids may be gone or mean other morsels after a reload
+        MORSEL_CO_LISTINGS
+            .write()
+            .map_err(|e| anyhow!(e.to_string()))?
+            .clear();
+        DATABASE_VERSION.fetch_add(1, Ordering::SeqCst);
         Ok(())
     } else {
         let database_path = match std::env::current_dir() {
@@ -123,3 +310,29 @@ pub async fn init_failed_keywords(config: &PluginConfig) -> anyhow::Result<()> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_every_see_also_cycle_once() {
+        let links = [
+            ("a", vec!["b".to_string()]),
+            ("b", vec!["c".to_string(), "d".to_string()]),
+            ("c", vec!["a".to_string()]),
+            ("d", vec![]),
+            ("e", vec!["a".to_string(), "e".to_string()]),
+        ];
+        let see_also = links
+            .iter()
+            .map(|(id, see_also)| (*id, see_also.as_slice()))
+            .collect::<HashMap<_, _>>();
+        let mut done = HashSet::new();
+        let mut cycles = Vec::new();
+        for (id, _see_also) in &links {
+            find_cycles(id, &see_also, &mut Vec::new(), &mut done, &mut cycles);
+        }
+        assert_eq!(cycles, vec![vec!["a", "b", "c", "a"], vec!["e", "e"]]);
+    }
+}
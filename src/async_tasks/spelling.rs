@@ -0,0 +1,197 @@
+//! SymSpell style spelling correction
+//!
+//! The dictionary is built from the morsel keywords and the vocabulary of the morsel
+//! contents at load time. Lookups use precomputed deletes, so correcting a query
+//! token does not require comparing it against every word of the dictionary.
+
+use super::morsels::MorselEntry;
+use std::collections::{HashMap, HashSet};
+
+/// Frequency boost for keyword vocabulary, keywords are preferred over content words
+const KEYWORD_BOOST: usize = 1000;
+
+/// Content words shorter than this are not added to the dictionary
+const MIN_WORD_LEN: usize = 3;
+
+#[derive(Debug)]
+pub struct SpellingDictionary {
+    words: HashMap<String, usize>,
+    deletes: HashMap<String, Vec<String>>,
+    max_distance: usize,
+}
+
+impl SpellingDictionary {
+    pub fn new(entries: &[MorselEntry], max_distance: usize) -> Self {
+        let mut words: HashMap<String, usize> = HashMap::new();
+        for entry in entries {
+            entry
+                .keywords
+                .iter()
+                .flat_map(|keyword| words_of(keyword))
+                .for_each(|word| *words.entry(word).or_default() += KEYWORD_BOOST);
+            words_of(&entry.content)
+                .filter(|word| word.chars().count() >= MIN_WORD_LEN)
+                .for_each(|word| *words.entry(word).or_default() += 1);
+        }
+
+        let mut deletes: HashMap<String, Vec<String>> = HashMap::new();
+        for word in words.keys() {
+            for delete in edits(word, max_distance) {
+                deletes.entry(delete).or_default().push(word.clone());
+            }
+        }
+
+        Self {
+            words,
+            deletes,
+            max_distance,
+        }
+    }
+
+    /// Correct every word of a keyword, returns None if no word needed correction
+    pub fn correct(&self, keyword: &str) -> Option<String> {
+        let mut corrected = false;
+        let words = keyword
+            .split_whitespace()
+            .map(|word| match self.lookup(word) {
+                Some(correction) => {
+                    corrected = true;
+                    correction
+                }
+                None => word.to_string(),
+            })
+            .collect::<Vec<_>>();
+        corrected.then(|| words.join(" "))
+    }
+
+    /// Closest dictionary word by edit distance, ties are broken by frequency.
+    /// Returns None if the word is known, not correctable or has no close neighbour.
+    fn lookup(&self, word: &str) -> Option<String> {
+        let word = word.to_lowercase();
+        if self.words.contains_key(&word) || !word.chars().all(char::is_alphabetic) {
+            return None;
+        }
+        // short words allow fewer edits, "tls" must not become "all"
+        let max_distance = self
+            .max_distance
+            .min(word.chars().count().saturating_sub(1) / 3);
+        if max_distance == 0 {
+            return None;
+        }
+
+        let mut candidates = HashSet::new();
+        for delete in edits(&word, max_distance) {
+            if let Some(suggestions) = self.deletes.get(&delete) {
+                candidates.extend(suggestions.iter());
+            }
+        }
+
+        candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let distance = edit_distance(&word, candidate);
+                (distance <= max_distance).then_some((distance, candidate))
+            })
+            .min_by(|(d1, w1), (d2, w2)| {
+                d1.cmp(d2)
+                    .then_with(|| self.words[*w2].cmp(&self.words[*w1]))
+                    .then_with(|| w1.cmp(w2))
+            })
+            .map(|(_distance, candidate)| candidate.clone())
+    }
+}
+
+fn words_of(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+/// The word itself and all strings derived from it by up to max_distance deletes
+fn edits(word: &str, max_distance: usize) -> HashSet<String> {
+    let mut result = HashSet::from([word.to_string()]);
+    let mut frontier = vec![word.to_string()];
+    for _ in 0..max_distance {
+        let mut next = Vec::new();
+        for current in &frontier {
+            let chars = current.chars().collect::<Vec<_>>();
+            for i in 0..chars.len() {
+                let delete = chars
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, c)| *c)
+                    .collect::<String>();
+                if result.insert(delete.clone()) {
+                    next.push(delete);
+                }
+            }
+        }
+        frontier = next;
+    }
+    result
+}
+
+/// Optimal string alignment distance (levenshtein with adjacent transpositions)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    (0..=a.len()).for_each(|i| d[i][0] = i);
+    (0..=b.len()).for_each(|j| d[0][j] = j);
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary() -> SpellingDictionary {
+        let entries: Vec<MorselEntry> = serde_yaml::from_str(
+            "- id: security_overview
+  keywords: [ security, encryption, tls ]
+  content: All chat data is encrypted at rest.
+- id: data_retention
+  keywords: [ retention, history ]
+  content: Chat history is stored for thirty days.",
+        )
+        .unwrap();
+        SpellingDictionary::new(&entries, 2)
+    }
+
+    #[test]
+    fn corrects_misspelled_words() {
+        assert_eq!(dictionary().correct("encryptn").as_deref(), Some("encryption"));
+        assert_eq!(dictionary().correct("secruity").as_deref(), Some("security"));
+        assert_eq!(dictionary().correct("chat histroy").as_deref(), Some("chat history"));
+        // content words are known as well
+        assert_eq!(dictionary().correct("encrpyted").as_deref(), Some("encrypted"));
+    }
+
+    #[test]
+    fn keeps_known_short_and_numeric_words() {
+        assert_eq!(dictionary().correct("security"), None);
+        assert_eq!(dictionary().correct("tls"), None);
+        // short words allow no edits, "all" is in the dictionary
+        assert_eq!(dictionary().correct("als"), None);
+        assert_eq!(dictionary().correct("aes256"), None);
+    }
+
+    #[test]
+    fn edit_distance_counts_transpositions_once() {
+        assert_eq!(edit_distance("secruity", "security"), 1);
+        assert_eq!(edit_distance("encryptn", "encryption"), 2);
+        assert_eq!(edit_distance("", "tls"), 3);
+    }
+}
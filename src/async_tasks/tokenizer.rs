@@ -0,0 +1,106 @@
+//! Query tokenization
+//!
+//! Keywords are separated by commas, spaces or ideographic punctuation. Version numbers
+//! ("tls 1.3"), hyphenated technical terms ("aes-256") and alphanumeric codes ("oauth2", "s3")
+//! are kept as single tokens.
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// How numeric parts of a query ("1.3" in "tls 1.3") are matched against keywords
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NumericMatch {
+    /// numeric parts contribute to the trigram similarity like any other character
+    Fuzzy,
+    /// a keyword only matches if it contains exactly the numeric parts of the query
+    Exact,
+}
+
+// CJK queries are separated by ideographic punctuation rather than spaces
+const SEPARATORS: [char; 5] = [',', '，', '、', '；', '\u{3000}'];
+
+/// Punctuation trimmed from the start and end of a token, '.' only from the end
+/// as it starts terms like ".net"
+const TRIM: [char; 10] = ['.', '?', '!', ':', ';', '"', '\'', '(', ')', '。'];
+
+/// Split a keyword string into tokens. A version number following a word is
+/// attached to it, so "tls 1.3" stays one token while "security privacy" becomes two.
+pub fn split_keywords(keywords: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for phrase in keywords.split(SEPARATORS) {
+        let mut attach = false;
+        for word in phrase.split_whitespace() {
+            let word = word
+                .trim_start_matches(|c| c != '.' && TRIM.contains(&c))
+                .trim_end_matches(TRIM);
+            if word.is_empty() {
+                continue;
+            }
+            match tokens.last_mut() {
+                Some(last) if attach && is_version(word) => {
+                    last.push(' ');
+                    last.push_str(word);
+                    attach = false;
+                }
+                _ => {
+                    tokens.push(word.to_string());
+                    attach = !is_version(word);
+                }
+            }
+        }
+    }
+    tokens
+}
+
+/// "1.3", "2", "v2", "3.0.1"
+fn is_version(word: &str) -> bool {
+    let digits = word.strip_prefix(['v', 'V']).unwrap_or(word);
+    !digits.is_empty()
+        && digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.ends_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Numeric parts of a string, "tls 1.3" -> {"1.3"}, "aes-256" -> {"256"}, "oauth2" -> {"2"}
+pub fn numeric_parts(s: &str) -> HashSet<String> {
+    s.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|part| part.trim_matches('.'))
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_commas_and_spaces() {
+        assert_eq!(
+            split_keywords("security, privacy policy"),
+            vec!["security", "privacy", "policy"]
+        );
+        assert_eq!(split_keywords("数据，安全、价格"), vec!["数据", "安全", "价格"]);
+        assert_eq!(split_keywords(" (pricing)? "), vec!["pricing"]);
+    }
+
+    #[test]
+    fn keeps_versions_and_codes() {
+        assert_eq!(split_keywords("tls 1.3 gdpr"), vec!["tls 1.3", "gdpr"]);
+        assert_eq!(split_keywords("tls v1.3"), vec!["tls v1.3"]);
+        assert_eq!(split_keywords("aes-256, oauth2"), vec!["aes-256", "oauth2"]);
+        assert_eq!(split_keywords("1.3 tls"), vec!["1.3", "tls"]);
+        assert_eq!(split_keywords(".net, (.net)?"), vec![".net", ".net"]);
+        assert_eq!(split_keywords("asp.net core."), vec!["asp.net", "core"]);
+    }
+
+    #[test]
+    fn numeric_parts_of_keywords() {
+        assert_eq!(numeric_parts("tls 1.3"), HashSet::from(["1.3".to_string()]));
+        assert_eq!(numeric_parts("aes-256"), HashSet::from(["256".to_string()]));
+        assert_eq!(numeric_parts("oauth2"), HashSet::from(["2".to_string()]));
+        assert!(numeric_parts("security.").is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+//! Transliteration of Cyrillic and Greek script to Latin
+//!
+//! A lightweight replacement for ICU's Any-Latin transform covering the scripts
+//! our personas are queried in. Characters of other scripts are kept unchanged.
+
+fn cyrillic(ch: char) -> Option<&'static str> {
+    let latin = match ch {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' => "g",
+        'ґ' => "g",
+        'д' => "d",
+        'е' => "e",
+        'ё' => "e",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' => "i",
+        'і' => "i",
+        'ї' => "yi",
+        'й' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' => "",
+        'ы' => "y",
+        'ь' => "",
+        'э' => "e",
+        'ю' => "yu",
+        'я' => "ya",
+        _ => return None,
+    };
+    Some(latin)
+}
+
+fn greek(ch: char) -> Option<&'static str> {
+    let latin = match ch {
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' => "i",
+        'θ' => "th",
+        'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        'ω' | 'ώ' => "o",
+        _ => return None,
+    };
+    Some(latin)
+}
+
+/// Transliterate a string to Latin script.
+/// Returns None if the string contains no transliterable characters.
+pub fn transliterate(s: &str) -> Option<String> {
+    let mut changed = false;
+    let latin = s
+        .to_lowercase()
+        .chars()
+        .map(|ch| match cyrillic(ch).or_else(|| greek(ch)) {
+            Some(latin) => {
+                changed = true;
+                latin.to_string()
+            }
+            None => ch.to_string(),
+        })
+        .collect::<String>();
+    changed.then_some(latin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transliterates_cyrillic_and_greek() {
+        assert_eq!(transliterate("Привет").as_deref(), Some("privet"));
+        assert_eq!(transliterate("щука").as_deref(), Some("shchuka"));
+        assert_eq!(transliterate("Αθήνα").as_deref(), Some("athina"));
+        assert_eq!(transliterate("gdpr защита").as_deref(), Some("gdpr zashchita"));
+    }
+
+    #[test]
+    fn latin_is_unchanged() {
+        assert_eq!(transliterate("security"), None);
+        assert_eq!(transliterate("数据"), None);
+    }
+}
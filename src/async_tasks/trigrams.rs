@@ -1,16 +1,69 @@
+use super::keyboard::{typo_similarity, KeyboardLayout};
+use super::tokenizer::{numeric_parts, NumericMatch};
+use super::transliterate::transliterate;
 use anyhow::anyhow;
-use serde::Serialize;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
 
+/// Weight of the CJK bigram coverage against the bigram similarity. Below 1, so an exact
+/// match beats a keyword found inside the query, and longer keywords found beat shorter ones.
+const CJK_COVERAGE_WEIGHT: f64 = 0.9;
+/// Weight of single CJK character matches, below bigram matches of the same coverage
+const CJK_UNIGRAM_WEIGHT: f64 = 0.6;
+
 pub trait Named {
+    /// unique id of the item
+    fn id(&self) -> &str;
     fn names(&self) -> &[String];
 }
 
+#[derive(Debug)]
+struct NameTrigrams {
+    tag: String,
+    /// keyword as given in the database
+    keyword: String,
+    /// normalized or transliterated form of the keyword that is indexed
+    name: String,
+    trigrams: HashSet<String>,
+    numbers: HashSet<String>,
+    cjk_chars: HashSet<char>,
+}
+
+impl NameTrigrams {
+    fn new(tag: String, keyword: String, name: String) -> Self {
+        Self {
+            tag,
+            keyword,
+            trigrams: trigrams(&name),
+            numbers: numeric_parts(&name),
+            cjk_chars: cjk_chars(&name),
+            name,
+        }
+    }
+}
+
+struct Query {
+    text: String,
+    trigrams: HashSet<String>,
+    numbers: HashSet<String>,
+    cjk_chars: HashSet<char>,
+}
+
+/// Rescoring of trigram candidates by keyboard adjacency aware edit distance
+#[derive(Debug, Clone, Copy)]
+pub struct TypoScoring {
+    pub layout: KeyboardLayout,
+    pub weight: f64,
+}
+
 #[derive(Debug)]
 pub struct Trigrams<T: Named + Clone + Serialize> {
     item_map: HashMap<String, T>,
-    trigrams: Vec<(String, HashSet<String>)>,
+    trigrams: Vec<NameTrigrams>,
+    typo_scoring: Option<TypoScoring>,
+    transliteration: bool,
+    numeric_match: NumericMatch,
 }
 
 impl<T: Named + Clone + Serialize + Debug> Trigrams<T> {
@@ -19,67 +72,330 @@ impl<T: Named + Clone + Serialize + Debug> Trigrams<T> {
         let mut item_map = HashMap::new();
         for item in items {
             let names = item.names();
-            let tag = names
-                .first()
-                .ok_or_else(|| anyhow!("no names found"))?
-                .to_string();
-            names
-                .iter()
-                .for_each(|name| trigram_list.push((tag.clone(), trigrams(name))));
+            if names.is_empty() {
+                return Err(anyhow!("no names found for '{}'", item.id()));
+            }
+            let tag = item.id().to_string();
+            if item_map.contains_key(&tag) {
+                return Err(anyhow!("duplicate id '{tag}'"));
+            }
+            names.iter().for_each(|name| {
+                trigram_list.push(NameTrigrams::new(tag.clone(), name.clone(), name.to_lowercase()))
+            });
             item_map.insert(tag, item);
         }
 
         Ok(Self {
             item_map,
             trigrams: trigram_list,
+            typo_scoring: None,
+            transliteration: false,
+            numeric_match: NumericMatch::Fuzzy,
         })
     }
 
-    pub fn search(&self, str: &str, n_first: usize, min_score: f64) -> Vec<Match<T>> {
-        let cmp = trigrams(str);
-        let mut non_zero_matches = if min_score > 0.0 {
-            self.trigrams
-                .iter()
-                .map(|(name, trigrams)| (name, trigram_similarity(trigrams, &cmp)))
-                .filter(|(_name, score)| *score >= min_score)
-                .collect::<Vec<_>>()
-        } else {
-            // returning zero score results makes no sense
-            self.trigrams
+    /// Rescore trigram candidates with a keyboard adjacency aware typo similarity.
+    pub fn with_typo_scoring(mut self, typo_scoring: Option<TypoScoring>) -> Self {
+        self.typo_scoring = typo_scoring;
+        self
+    }
+
+    /// All indexed names in lowercase
+    pub fn vocabulary(&self) -> impl Iterator<Item = &str> {
+        self.trigrams.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// Index the Latin transliteration of Cyrillic and Greek names in addition to the original.
+    /// Queries are then searched in their original and transliterated form.
+    pub fn with_transliteration(mut self, transliteration: bool) -> Self {
+        self.transliteration = transliteration;
+        if transliteration {
+            let transliterated = self
+                .trigrams
                 .iter()
-                .map(|(name, trigrams)| (name, trigram_similarity(trigrams, &cmp)))
-                .filter(|(_name, score)| *score > min_score)
-                .collect::<Vec<_>>()
-        };
+                .filter_map(|entry| {
+                    transliterate(&entry.name).map(|name| {
+                        NameTrigrams::new(entry.tag.clone(), entry.keyword.clone(), name)
+                    })
+                })
+                .collect::<Vec<_>>();
+            self.trigrams.extend(transliterated);
+        }
+        self
+    }
 
-        non_zero_matches.sort_by(|a, b| {
-            // reverse sort order, sort descending
-            b.1.partial_cmp(&a.1).unwrap()
-        });
+    /// Require queries containing numbers to match them exactly or only fuzzily
+    pub fn with_numeric_match(mut self, numeric_match: NumericMatch) -> Self {
+        self.numeric_match = numeric_match;
+        self
+    }
 
-        non_zero_matches
+    pub fn search(&self, str: &str, n_first: usize, min_score: f64) -> Vec<Match<T>> {
+        self.search_matches(str, n_first, min_score, false)
+    }
+
+    /// Search with an explanation of how the score of every match came about
+    pub fn search_explained(&self, str: &str, n_first: usize, min_score: f64) -> Vec<Match<T>> {
+        self.search_matches(str, n_first, min_score, true)
+    }
+
+    fn search_matches(
+        &self,
+        str: &str,
+        n_first: usize,
+        min_score: f64,
+        explain: bool,
+    ) -> Vec<Match<T>> {
+        let queries = self.queries(str);
+        // an item may match by several names, keep its best one
+        let mut seen = HashSet::new();
+        self.scored(&queries, min_score)
             .into_iter()
+            .filter(|(entry, _score)| seen.insert(&entry.tag))
             .take(n_first)
-            .map(|(name, score)| Match {
+            .map(|(entry, score)| Match {
                 item: self
                     .item_map
-                    .get(name.as_str())
+                    .get(entry.tag.as_str())
                     .expect("name should exist in hashmap")
                     .clone(),
                 score,
+                query: str.to_string(),
+                keyword: entry.keyword.clone(),
+                // the query as given, a transliterated query equal to the name is no exact match
+                exact: queries[0].text == entry.keyword.to_lowercase(),
+                explanation: explain.then(|| self.explain(entry, &queries, min_score)),
+            })
+            .collect()
+    }
+
+    /// The database keywords closest to the query regardless of any minimum score
+    pub fn nearest_keywords(&self, str: &str, n_first: usize) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.scored(&self.queries(str), 0.0)
+            .into_iter()
+            .filter(|(entry, _score)| seen.insert(&entry.keyword))
+            .take(n_first)
+            .map(|(entry, _score)| entry.keyword.clone())
+            .collect()
+    }
+
+    /// Item by its id
+    pub fn get(&self, id: &str) -> Option<&T> {
+        self.item_map.get(id)
+    }
+
+    /// All items of the index
+    pub fn items(&self) -> impl Iterator<Item = &T> {
+        self.item_map.values()
+    }
+
+    /// The query in its original and, if enabled, transliterated form
+    fn queries(&self, str: &str) -> Vec<Query> {
+        let mut queries = vec![str.to_lowercase()];
+        if self.transliteration
+            && let Some(transliterated) = transliterate(str)
+        {
+            queries.push(transliterated);
+        }
+        queries
+            .into_iter()
+            .map(|text| Query {
+                trigrams: trigrams(&text),
+                numbers: numeric_parts(&text),
+                cjk_chars: cjk_chars(&text),
+                text,
             })
             .collect()
     }
+
+    /// All names with a non-zero score of at least min_score, best first
+    fn scored(&self, queries: &[Query], min_score: f64) -> Vec<(&NameTrigrams, f64)> {
+        let mut non_zero_matches = self
+            .trigrams
+            .iter()
+            .filter_map(|entry| {
+                let score = queries
+                    .iter()
+                    .map(|query| self.score(entry, query))
+                    .fold(0.0, f64::max);
+                // returning zero score results makes no sense
+                (score > 0.0 && score >= min_score).then_some((entry, score))
+            })
+            .collect::<Vec<_>>();
+
+        non_zero_matches.sort_by(|a, b| {
+            // reverse sort order, sort descending
+            b.1.partial_cmp(&a.1).unwrap()
+        });
+        non_zero_matches
+    }
+
+    fn score(&self, entry: &NameTrigrams, query: &Query) -> f64 {
+        if self.numeric_match == NumericMatch::Exact
+            && !query.numbers.is_empty()
+            && query.numbers != entry.numbers
+        {
+            return 0.0;
+        }
+        let cmp = &query.trigrams;
+        let trigram_score = trigram_similarity(&entry.trigrams, cmp);
+        let coverage_score = CJK_COVERAGE_WEIGHT * cjk_coverage(&entry.trigrams, cmp)
+            + (1.0 - CJK_COVERAGE_WEIGHT) * trigram_score;
+        let similarity = trigram_score
+            .max(coverage_score)
+            .max(CJK_UNIGRAM_WEIGHT * cjk_unigram_coverage(&entry.cjk_chars, &query.cjk_chars));
+        // only candidates sharing trigrams with the query are rescored
+        if similarity == 0.0 {
+            return 0.0;
+        }
+        match &self.typo_scoring {
+            Some(typo) => {
+                let typo_score = typo_similarity(&entry.name, &query.text, typo.layout);
+                // rescoring may raise a typo candidate but never demote a trigram match
+                similarity.max((1.0 - typo.weight) * similarity + typo.weight * typo_score)
+            }
+            None => similarity,
+        }
+    }
+
+    fn explain(&self, entry: &NameTrigrams, queries: &[Query], min_score: f64) -> Explanation {
+        // the query form the score was achieved with
+        let (index, query) = queries
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| {
+                self.score(entry, a)
+                    .partial_cmp(&self.score(entry, b))
+                    .unwrap()
+            })
+            .expect("there is at least the original query");
+        let mut shared_trigrams = entry
+            .trigrams
+            .intersection(&query.trigrams)
+            .cloned()
+            .collect::<Vec<_>>();
+        shared_trigrams.sort();
+
+        let mut components = BTreeMap::new();
+        components.insert(
+            "trigram_similarity".to_string(),
+            trigram_similarity(&entry.trigrams, &query.trigrams),
+        );
+        let coverage = cjk_coverage(&entry.trigrams, &query.trigrams);
+        if coverage > 0.0 {
+            components.insert("cjk_coverage".to_string(), coverage);
+            components.insert("cjk_coverage_weight".to_string(), CJK_COVERAGE_WEIGHT);
+        }
+        let unigram_coverage = cjk_unigram_coverage(&entry.cjk_chars, &query.cjk_chars);
+        if unigram_coverage > 0.0 {
+            components.insert("cjk_unigram_coverage".to_string(), unigram_coverage);
+            components.insert("cjk_unigram_weight".to_string(), CJK_UNIGRAM_WEIGHT);
+        }
+        if let Some(typo) = &self.typo_scoring {
+            components.insert(
+                "typo_similarity".to_string(),
+                typo_similarity(&entry.name, &query.text, typo.layout),
+            );
+            components.insert("typo_weight".to_string(), typo.weight);
+        }
+        components.insert("score".to_string(), self.score(entry, query));
+
+        let mut filters = vec![format!("min_score >= {min_score}")];
+        if self.numeric_match == NumericMatch::Exact && !query.numbers.is_empty() {
+            let mut numbers = query.numbers.iter().cloned().collect::<Vec<_>>();
+            numbers.sort();
+            filters.push(format!("exact numeric match of {numbers:?}"));
+        }
+        if index > 0 {
+            filters.push("transliterated query".to_string());
+        }
+        if entry.name != entry.keyword.to_lowercase() {
+            filters.push("transliterated keyword".to_string());
+        }
+
+        Explanation {
+            query: query.text.clone(),
+            keyword: entry.keyword.clone(),
+            union_size: entry.trigrams.union(&query.trigrams).count(),
+            shared_trigrams,
+            components,
+            filters,
+        }
+    }
 }
 
 #[derive(Clone, Serialize)]
 pub struct Match<T> {
     pub item: T,
     pub score: f64,
+    /// the searched query
+    pub query: String,
+    /// the name of the item the query matched
+    pub keyword: String,
+    /// true if the query equals the matched name, ignoring case
+    pub exact: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Explanation>,
+}
+
+/// How the score of a match came about
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Explanation {
+    /// query as searched, after normalization or transliteration
+    pub query: String,
+    /// database keyword the query matched
+    pub keyword: String,
+    pub shared_trigrams: Vec<String>,
+    pub union_size: usize,
+    /// named score components, "score" is the resulting score
+    pub components: BTreeMap<String, f64>,
+    /// filters and transformations applied
+    pub filters: Vec<String>,
+}
+
+/// true for characters of scripts written without spaces between words (CJK ideographs, kana)
+/// and for hangul, which are indexed as character bigrams instead of padded trigrams
+fn is_cjk(ch: char) -> bool {
+    matches!(ch,
+        '\u{3040}'..='\u{30ff}'     // hiragana, katakana
+        | '\u{3400}'..='\u{4dbf}'   // CJK extension A
+        | '\u{4e00}'..='\u{9fff}'   // CJK unified ideographs
+        | '\u{ac00}'..='\u{d7af}'   // hangul syllables
+        | '\u{f900}'..='\u{faff}'   // CJK compatibility ideographs
+        | '\u{ff66}'..='\u{ff9f}'   // halfwidth katakana
+        | '\u{20000}'..='\u{2a6df}' // CJK extension B
+    )
 }
 
 fn trigrams(s: &str) -> HashSet<String> {
     let s = s.to_lowercase();
+    if !s.chars().any(is_cjk) {
+        return padded_trigrams(&s);
+    }
+
+    // split into runs of CJK and other characters
+    let mut grams = HashSet::new();
+    let chars = s.chars().collect::<Vec<_>>();
+    for run in chars.chunk_by(|a, b| is_cjk(*a) == is_cjk(*b)) {
+        if is_cjk(run[0]) {
+            if run.len() == 1 {
+                grams.insert(run[0].to_string());
+            } else {
+                grams.extend(run.windows(2).map(|w| w.iter().collect::<String>()));
+            }
+        } else {
+            let run = run.iter().collect::<String>();
+            let run = run.trim();
+            if !run.is_empty() {
+                grams.extend(padded_trigrams(run));
+            }
+        }
+    }
+    grams
+}
+
+fn padded_trigrams(s: &str) -> HashSet<String> {
     let s = format!("  {s}  "); // pad with spaces like pg_trgm
     s.chars()
         .collect::<Vec<_>>()
@@ -88,6 +404,40 @@ fn trigrams(s: &str) -> HashSet<String> {
         .collect()
 }
 
+/// Share of the CJK bigrams of an indexed name found in the query.
+/// CJK queries come without spaces, so a name is matched inside a longer
+/// query run rather than by similarity of the whole query (dictionary free segmentation).
+fn cjk_coverage(trigram: &HashSet<String>, cmp: &HashSet<String>) -> f64 {
+    let (covered, total) = trigram
+        .iter()
+        .filter(|gram| gram.chars().all(is_cjk))
+        .fold((0, 0), |(covered, total), gram| {
+            (covered + usize::from(cmp.contains(gram)), total + 1)
+        });
+    if total == 0 {
+        0.0
+    } else {
+        covered as f64 / total as f64
+    }
+}
+
+/// 1 if a one character CJK name or query is found in the other, 0 otherwise.
+/// A fallback for one character words, which share no bigram with a longer text:
+/// the name "锁" inside the query "数据锁" and the query "价" for the name "价格".
+/// Longer names and queries are matched by their bigrams.
+fn cjk_unigram_coverage(name: &HashSet<char>, query: &HashSet<char>) -> f64 {
+    let shorter = if name.len() <= query.len() { name } else { query };
+    if shorter.len() == 1 && name.intersection(query).next().is_some() {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn cjk_chars(s: &str) -> HashSet<char> {
+    s.chars().filter(|ch| is_cjk(*ch)).collect()
+}
+
 fn trigram_similarity(trigram: &HashSet<String>, cmp: &HashSet<String>) -> f64 {
     let intersection = trigram.intersection(cmp).count() as f64;
     let union = trigram.union(cmp).count() as f64;
@@ -97,3 +447,111 @@ fn trigram_similarity(trigram: &HashSet<String>, cmp: &HashSet<String>) -> f64 {
         intersection / union
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Serialize)]
+    struct Item {
+        id: String,
+        names: Vec<String>,
+    }
+
+    impl Named for Item {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn names(&self) -> &[String] {
+            &self.names
+        }
+    }
+
+    fn index(names: &[&[&str]]) -> Trigrams<Item> {
+        let items = names
+            .iter()
+            .enumerate()
+            .map(|(i, names)| Item {
+                id: format!("item{i}"),
+                names: names.iter().map(|name| name.to_string()).collect(),
+            })
+            .collect();
+        Trigrams::new(items).unwrap()
+    }
+
+    fn best(trigrams: &Trigrams<Item>, query: &str) -> Option<(String, f64)> {
+        trigrams
+            .search(query, 1, 0.1)
+            .into_iter()
+            .next()
+            .map(|m| (m.keyword, m.score))
+    }
+
+    #[test]
+    fn exact_and_fuzzy_matches() {
+        let trigrams = index(&[&["security", "encryption"], &["pricing"]]);
+        let matches = trigrams.search("Security", 5, 0.1);
+        assert_eq!(matches[0].keyword, "security");
+        assert_eq!(matches[0].score, 1.0);
+        assert!(matches[0].exact);
+        let (keyword, score) = best(&trigrams, "encryptn").unwrap();
+        assert_eq!(keyword, "encryption");
+        assert!(score > 0.5 && score < 1.0);
+    }
+
+    #[test]
+    fn items_are_keyed_by_id() {
+        let trigrams = index(&[&["security", "encryption"], &["security", "tls"]]);
+        assert_eq!(trigrams.get("item0").unwrap().names[1], "encryption");
+        assert_eq!(trigrams.get("item1").unwrap().names[1], "tls");
+        assert!(trigrams.get("security").is_none());
+        assert_eq!(trigrams.search("security", 5, 0.1).len(), 2);
+
+        let duplicate = Item {
+            id: "item".to_string(),
+            names: vec!["gdpr".to_string()],
+        };
+        assert!(Trigrams::new(vec![duplicate.clone(), duplicate]).is_err());
+    }
+
+    #[test]
+    fn cjk_keyword_inside_a_longer_query() {
+        let trigrams = index(&[&["数据"], &["数据库"], &["锁"], &["价格"]]);
+        let (keyword, score) = best(&trigrams, "数据库安全").unwrap();
+        assert_eq!(keyword, "数据库");
+        assert!(score > CJK_COVERAGE_WEIGHT && score < 1.0);
+        // the exact match wins over a shorter keyword found inside the query
+        assert_eq!(best(&trigrams, "数据库").unwrap(), ("数据库".to_string(), 1.0));
+        let reversed = index(&[&["数据库"], &["数据"]]);
+        assert_eq!(best(&reversed, "数据库").unwrap(), ("数据库".to_string(), 1.0));
+        assert_eq!(best(&reversed, "数据").unwrap(), ("数据".to_string(), 1.0));
+        // one character words share no bigram with a longer text
+        let trigrams = index(&[&["数据库"], &["锁"], &["价格"]]);
+        assert_eq!(best(&trigrams, "数据锁").unwrap(), ("锁".to_string(), CJK_UNIGRAM_WEIGHT));
+        assert_eq!(best(&trigrams, "价").unwrap(), ("价格".to_string(), CJK_UNIGRAM_WEIGHT));
+        assert_eq!(best(&trigrams, "安全"), None);
+    }
+
+    #[test]
+    fn exact_numeric_match() {
+        let trigrams = index(&[&["tls 1.2"], &["tls 1.3"]]);
+        let fuzzy = trigrams.search("tls 1.3", 5, 0.1);
+        assert_eq!(fuzzy.len(), 2);
+        let exact = index(&[&["tls 1.2"], &["tls 1.3"]])
+            .with_numeric_match(NumericMatch::Exact)
+            .search("tls 1.3", 5, 0.1);
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].keyword, "tls 1.3");
+    }
+
+    #[test]
+    fn transliterated_keywords() {
+        let trigrams = index(&[&["защита данных"]]).with_transliteration(true);
+        let (keyword, score) = best(&trigrams, "zashchita dannykh").unwrap();
+        assert_eq!(keyword, "защита данных");
+        assert_eq!(score, 1.0);
+        assert!(!trigrams.search("zashchita dannykh", 1, 0.1)[0].exact);
+        assert!(trigrams.search("Защита данных", 1, 0.1)[0].exact);
+    }
+}
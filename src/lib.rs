@@ -6,15 +6,20 @@ use mcp_plugin_api::*;
 use once_cell::sync::Lazy;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::collections::HashMap;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::ops::Deref;
 use std::path::PathBuf;
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockReadGuard};
 
 mod async_tasks;
-use async_tasks::{run_async_tasks, Trigrams};
+use async_tasks::{
+    run_async_tasks, significant_words, split_keywords, CompoundSplitter, Explanation, KeyboardLayout,
+    ExtractedKeyword, KeywordExtractor, Match, NumericMatch, SpellingDictionary, Trigrams,
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DbMorsel {
@@ -22,6 +27,148 @@ pub struct DbMorsel {
     pub content: String,
     pub link: Option<String>,
     pub score: f32, // Useful for the LLM to see confidence
+    /// query keyword that found this morsel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_keyword: Option<String>,
+    /// database keyword the query keyword matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_keyword: Option<String>,
+    /// true if the query keyword equals the matched keyword, ignoring case
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exact_match: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Explanation>,
+    /// other morsels to offer as follow-up topics
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<RelatedTopic>,
+    /// id of the returned morsel whose see also links pulled in this morsel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linked_from: Option<String>,
+}
+
+impl DbMorsel {
+    fn new(item: &MorselEntry, score: f64) -> Self {
+        Self {
+            id: item.id.clone(),
+            content: item.content.clone(),
+            link: item.link.clone(),
+            score: score as f32,
+            query_keyword: None,
+            matched_keyword: None,
+            exact_match: None,
+            confidence: None,
+            explanation: None,
+            related: vec![],
+            linked_from: None,
+        }
+    }
+
+    fn from_match(m: Match<MorselEntry>) -> Self {
+        Self {
+            query_keyword: Some(m.query),
+            matched_keyword: Some(m.keyword),
+            exact_match: Some(m.exact),
+            explanation: m.explanation,
+            ..Self::new(&m.item, m.score)
+        }
+    }
+
+    fn with_confidence(mut self, bands: &ConfidenceBands) -> Self {
+        self.confidence = Some(bands.band(self.score as f64));
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Confidence {
+    High,
+    Medium,
+    Low,
+}
+
+/// Score thresholds of the confidence bands and the guidance given to the LLM per band
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(default)]
+pub struct ConfidenceBands {
+    /// Minimum score of a high confidence morsel
+    #[schemars(range(min = 0.0, max = 1.0))]
+    high: f64,
+    /// Minimum score of a medium confidence morsel, lower scores are low confidence
+    #[schemars(range(min = 0.0, max = 1.0))]
+    medium: f64,
+    high_guidance: String,
+    medium_guidance: String,
+    low_guidance: String,
+}
+
+impl Default for ConfidenceBands {
+    fn default() -> Self {
+        Self {
+            high: 0.7,
+            medium: 0.4,
+            high_guidance: "high confidence: answer directly from this morsel".into(),
+            medium_guidance: "medium confidence: check that the morsel fits the question".into(),
+            low_guidance: "low confidence: verify with the user before relying on this".into(),
+        }
+    }
+}
+
+impl ConfidenceBands {
+    fn band(&self, score: f64) -> Confidence {
+        if score >= self.high {
+            Confidence::High
+        } else if score >= self.medium {
+            Confidence::Medium
+        } else {
+            Confidence::Low
+        }
+    }
+
+    /// Guidance for the bands present in the returned morsels
+    fn guidance(&self, morsels: &[DbMorsel]) -> BTreeMap<Confidence, String> {
+        morsels
+            .iter()
+            .filter_map(|morsel| morsel.confidence)
+            .map(|confidence| {
+                let guidance = match confidence {
+                    Confidence::High => &self.high_guidance,
+                    Confidence::Medium => &self.medium_guidance,
+                    Confidence::Low => &self.low_guidance,
+                };
+                (confidence, guidance.clone())
+            })
+            .collect()
+    }
+}
+
+/// Morsel related to a returned morsel, named by its first keyword
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RelatedTopic {
+    pub id: String,
+    pub keyword: String,
+    pub reason: RelatedReason,
+}
+
+/// Why a topic is related, in order of precedence
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum RelatedReason {
+    /// listed in the 'related' field of the morsel
+    Linked,
+    /// frequently returned together with the morsel
+    CoListed,
+    /// has keywords in common with the morsel
+    SharedKeywords,
+}
+
+/// Spelling correction applied to a query keyword before searching
+#[derive(Serialize, Debug, Clone)]
+pub struct Correction {
+    pub keyword: String,
+    pub corrected: String,
 }
 
 #[derive(Serialize, Debug)]
@@ -30,13 +177,177 @@ pub enum ToolResponse {
     Success {
         results_count: usize,
         morsels: Vec<DbMorsel>,
+        guidance: BTreeMap<Confidence, String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        corrections: Vec<Correction>,
+        /// keywords extracted from the 'question' parameter
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        extracted_keywords: Vec<String>,
+        /// opaque token for the next page of results, see 'continuation'
+        #[serde(skip_serializing_if = "Option::is_none")]
+        continuation: Option<String>,
+    },
+    /// Some query keywords matched, the others have no coverage in the database
+    PartialMatch {
+        results_count: usize,
+        morsels: Vec<DbMorsel>,
+        guidance: BTreeMap<Confidence, String>,
+        unmatched_keywords: Vec<UnmatchedKeyword>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        corrections: Vec<Correction>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        extracted_keywords: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        continuation: Option<String>,
+    },
+    /// Morsels grouped by query keyword, see 'response_layout'
+    Grouped {
+        results_count: usize,
+        groups: Vec<KeywordGroup>,
+        guidance: BTreeMap<Confidence, String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        corrections: Vec<Correction>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        extracted_keywords: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        continuation: Option<String>,
     },
     NoMatch {
         searched_keywords: Vec<String>,
-        suggestion: String,
+        suggested_keywords: Vec<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        corrections: Vec<Correction>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fallback: Option<Box<DbMorsel>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        escalation: Option<Escalation>,
     },
 }
 
+#[derive(Serialize, Debug)]
+pub struct KeywordGroup {
+    pub keyword: String,
+    pub status: GroupStatus,
+    pub morsels: Vec<DbMorsel>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub near_misses: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupStatus {
+    Matched,
+    /// the keyword matched, but its morsels are listed under another keyword, excluded,
+    /// or left for the next page
+    NoNewMorsels,
+    NoMatch,
+}
+
+/// Layout of the morsels in a 'keywords_to_morsel' response
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseLayout {
+    /// one list sorted by score
+    Flat,
+    /// morsels grouped under the query keyword that matched them
+    Grouped,
+}
+
+/// Query keyword without matches and the database keywords nearest to it
+#[derive(Serialize, Debug)]
+pub struct UnmatchedKeyword {
+    pub keyword: String,
+    pub near_misses: Vec<String>,
+}
+
+/// Escalation information for the LLM if the database has no answer
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct Escalation {
+    /// What the persona should tell the user, e.g. "Our support team can help with this."
+    message: String,
+    /// Contact for escalation, e.g. a support email address
+    contact: Option<String>,
+    /// Link to further documentation, e.g. the docs index
+    link: Option<String>,
+}
+
+/// Position in a paginated search. Handed to the LLM as an opaque token, which is the
+/// hex encoded JSON of this struct
+#[derive(Serialize, Deserialize, Debug)]
+struct Continuation {
+    keywords: Vec<String>,
+    /// database version the previous pages were ranked on
+    version: u64,
+    /// ids of the morsels returned on previous pages and of the excluded morsels
+    seen: Vec<String>,
+    per_keyword_n_best: usize,
+    max_total_results: usize,
+    min_score: f64,
+}
+
+impl Continuation {
+    fn encode(&self) -> Result<String, String> {
+        let json = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        Ok(json.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+
+    fn decode(token: &str, config: &PluginConfig) -> Result<Self, String> {
+        let invalid = || "Invalid parameter 'continuation', not a continuation token".to_string();
+        if !token.len().is_multiple_of(2) || !token.is_ascii() {
+            return Err(invalid());
+        }
+        let json = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let mut continuation: Self = serde_json::from_slice(&json).map_err(|_| invalid())?;
+        if continuation.version != DATABASE_VERSION.load(Ordering::SeqCst) {
+            return Err("Continuation token expired, the database was reloaded. \
+                Repeat the search without 'continuation'"
+                .into());
+        }
+
+        // the token comes from the client, a forged one must not bypass the configured bounds
+        let n_best = config.morsel_n_best.unwrap();
+        let page_limit = config
+            .max_results_limit
+            .unwrap()
+            .max(config.per_keyword_n_best.unwrap_or(n_best))
+            .max(config.max_total_results.unwrap_or(n_best));
+        continuation.per_keyword_n_best = continuation.per_keyword_n_best.min(page_limit).max(1);
+        continuation.max_total_results = continuation.max_total_results.min(page_limit).max(1);
+        let score_floor = config
+            .min_score_floor
+            .unwrap()
+            .min(config.morsel_min_score.unwrap());
+        continuation.min_score = continuation.min_score.min(1.0).max(score_floor);
+        Ok(continuation)
+    }
+}
+
+/// Page of the topics covered by the database, see 'list_topics'
+#[derive(Serialize, Debug)]
+pub struct TopicList {
+    /// number of topics matching the category filter
+    pub total_count: usize,
+    pub topics: Vec<Topic>,
+    /// offset of the next page, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Topic {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// the first keywords of the morsel
+    pub keywords: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FailLogEntry {
     keyword: String,
@@ -47,6 +358,49 @@ pub struct FailLogEntry {
 static MORSEL_TRIGRAMS: Lazy<RwLock<Option<Trigrams<MorselEntry>>>> =
     Lazy::new(|| RwLock::new(None));
 
+/// Read access to the loaded morsel index, see 'morsel_index'
+struct MorselIndex(RwLockReadGuard<'static, Option<Trigrams<MorselEntry>>>);
+
+impl Deref for MorselIndex {
+    type Target = Trigrams<MorselEntry>;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref().expect("checked by morsel_index")
+    }
+}
+
+/// The loaded morsel index, an error if the data is not initialized yet
+fn morsel_index() -> Result<MorselIndex, String> {
+    let trigrams = MORSEL_TRIGRAMS
+        .read()
+        .map_err(|e| format!("cannot read directory entries: {e}"))?;
+    if trigrams.is_none() {
+        return Err("Morsel data is not initialized".into());
+    }
+    Ok(MorselIndex(trigrams))
+}
+
+static MORSEL_COMPOUNDS: Lazy<RwLock<Option<CompoundSplitter>>> =
+    Lazy::new(|| RwLock::new(None));
+
+static MORSEL_EXTRACTOR: Lazy<RwLock<Option<KeywordExtractor>>> =
+    Lazy::new(|| RwLock::new(None));
+
+static MORSEL_SPELLING: Lazy<RwLock<Option<SpellingDictionary>>> =
+    Lazy::new(|| RwLock::new(None));
+
+/// Number of responses each morsel id was returned in
+static MORSEL_HITS: Lazy<RwLock<HashMap<String, usize>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Number of responses each pair of morsel ids was returned together in since the
+/// database was loaded
+static MORSEL_CO_LISTINGS: Lazy<RwLock<HashMap<String, HashMap<String, usize>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Incremented on every database load, continuation tokens of older versions are rejected
+static DATABASE_VERSION: AtomicU64 = AtomicU64::new(0);
+
 static FAILED_KEYWORDS: Lazy<RwLock<Option<HashMap<String, FailLogEntry>>>> =
     Lazy::new(|| RwLock::new(None));
 
@@ -63,17 +417,121 @@ pub struct PluginConfig {
     database_path: PathBuf,
     /// Path to failed keyword log
     failed_keywords_path: Option<PathBuf>,
-    /// Maximum number of candidates to retrieve in fuzzy card name search
+    /// Maximum number of candidates to retrieve in fuzzy card name search.
+    /// Default for 'per_keyword_n_best' and 'max_total_results'
     #[schemars(range(min = 1, max = 10))]
     #[serde(default = "directory_n_best")]
     morsel_n_best: Option<usize>,
+    /// Maximum number of candidates retrieved per query keyword
+    #[schemars(range(min = 1, max = 10))]
+    per_keyword_n_best: Option<usize>,
+    /// Maximum number of morsels returned for all query keywords
+    #[schemars(range(min = 1, max = 10))]
+    max_total_results: Option<usize>,
+    /// Return at least one morsel per matched query keyword, even beyond 'max_total_results'
+    #[serde(default = "default_guarantee_per_keyword")]
+    guarantee_per_keyword: Option<bool>,
+    /// Diversity re-ranking by maximal marginal relevance, 1.0 ranks by score only,
+    /// lower values penalize morsels similar to better ones. Disabled if not set
+    #[schemars(range(min = 0.0, max = 1.0))]
+    mmr_lambda: Option<f64>,
+    /// Similarity between morsels used for diversity re-ranking
+    #[serde(default = "default_mmr_similarity")]
+    mmr_similarity: Option<MorselSimilarity>,
     /// Minimum score for a candidate in fuzzy card name search to make it to the result list
     #[schemars(range(min = 0.0, max = 1.0))]
     #[serde(default = "directory_min_score")]
     morsel_min_score: Option<f64>,
+    /// Upper bound for the 'max_results' parameter of a 'keywords_to_morsel' call
+    #[schemars(range(min = 1, max = 50))]
+    #[serde(default = "default_max_results_limit")]
+    max_results_limit: Option<usize>,
+    /// Lower bound for the 'min_score' parameter of a 'keywords_to_morsel' call
+    #[schemars(range(min = 0.0, max = 1.0))]
+    #[serde(default = "default_min_score_floor")]
+    min_score_floor: Option<f64>,
     #[schemars(range(min = 120))]
     #[serde(default = "default_update_interval_secs")]
     update_interval_secs: Option<u32>,
+    /// Keyboard layout used to rescore fuzzy matches, adjacent key typos are cheaper. Disabled if not set
+    typo_layout: Option<KeyboardLayout>,
+    /// Weight of the keyboard typo similarity when rescoring trigram candidates
+    #[schemars(range(min = 0.0, max = 1.0))]
+    #[serde(default = "default_typo_weight")]
+    typo_weight: Option<f64>,
+    /// Decompose compound keywords into parts of the database vocabulary and search each part
+    #[serde(default = "default_compound_splitting")]
+    compound_splitting: Option<bool>,
+    /// Minimum length of a compound part
+    #[schemars(range(min = 2))]
+    #[serde(default = "default_compound_min_part_len")]
+    compound_min_part_len: Option<usize>,
+    /// Index and search Cyrillic and Greek keywords in their Latin transliteration as well
+    #[serde(default = "default_transliteration")]
+    transliteration: Option<bool>,
+    /// Match numeric parts of keywords ("tls 1.3", "aes-256") exactly or fuzzily
+    #[serde(default = "default_numeric_match")]
+    numeric_match: Option<NumericMatch>,
+    /// Correct misspelled keywords against the database vocabulary before searching
+    #[serde(default = "default_spelling_correction")]
+    spelling_correction: Option<bool>,
+    /// Maximum edit distance of a spelling correction
+    #[schemars(range(min = 1, max = 3))]
+    #[serde(default = "default_spelling_max_distance")]
+    spelling_max_distance: Option<usize>,
+    /// Keywords whose best fuzzy match scores below this threshold are spell corrected
+    #[schemars(range(min = 0.0, max = 1.0))]
+    #[serde(default = "default_spelling_threshold")]
+    spelling_threshold: Option<f64>,
+    /// Maximum number of suggested keywords returned if nothing matches
+    #[schemars(range(min = 1, max = 20))]
+    #[serde(default = "default_suggestion_count")]
+    suggestion_count: Option<usize>,
+    /// Return morsels as one list sorted by score or grouped by query keyword
+    #[serde(default = "default_response_layout")]
+    response_layout: Option<ResponseLayout>,
+    /// Allow the 'explain' parameter of 'keywords_to_morsel', which returns how each result matched
+    #[serde(default = "default_allow_explain")]
+    allow_explain: Option<bool>,
+    /// Minimum score of words extracted from a question that are not in the keyword vocabulary
+    #[schemars(range(min = 0.0, max = 1.0))]
+    #[serde(default = "default_extraction_min_score")]
+    extraction_min_score: Option<f64>,
+    /// Weight of the overlap with the 'context' parameter when re-ranking matches, 0 disables it
+    #[schemars(range(min = 0.0, max = 1.0))]
+    #[serde(default = "default_context_weight")]
+    context_weight: Option<f64>,
+    /// Maximum number of see also links followed from a returned morsel
+    #[schemars(range(min = 1, max = 5))]
+    #[serde(default = "default_see_also_max_depth")]
+    see_also_max_depth: Option<usize>,
+    /// Maximum number of morsels added to a response by following see also links
+    #[schemars(range(min = 1, max = 10))]
+    #[serde(default = "default_see_also_max_count")]
+    see_also_max_count: Option<usize>,
+    /// Maximum number of related topics returned per morsel, 0 disables related topics
+    #[schemars(range(max = 10))]
+    #[serde(default = "default_related_count")]
+    related_count: Option<usize>,
+    /// Minimum number of responses two morsels were returned together in to be related
+    #[schemars(range(min = 1))]
+    #[serde(default = "default_co_listing_min_count")]
+    co_listing_min_count: Option<usize>,
+    /// Number of topics per page of 'list_topics'
+    #[schemars(range(min = 1, max = 100))]
+    #[serde(default = "default_topics_page_size")]
+    topics_page_size: Option<usize>,
+    /// Number of representative keywords per topic of 'list_topics'
+    #[schemars(range(min = 1, max = 10))]
+    #[serde(default = "default_topic_keyword_count")]
+    topic_keyword_count: Option<usize>,
+    /// Score thresholds and per band guidance for the confidence of returned morsels
+    #[serde(default = "default_confidence_bands")]
+    confidence_bands: Option<ConfidenceBands>,
+    /// Id of a morsel returned if nothing matches, e.g. pointing to support or the docs index
+    fallback_morsel_id: Option<String>,
+    /// Escalation information returned if nothing matches
+    escalation: Option<Escalation>,
 }
 
 fn directory_n_best() -> Option<usize> {
@@ -84,14 +542,110 @@ fn directory_min_score() -> Option<f64> {
     Some(0.2)
 }
 
+fn default_guarantee_per_keyword() -> Option<bool> {
+    Some(false)
+}
+
+fn default_mmr_similarity() -> Option<MorselSimilarity> {
+    Some(MorselSimilarity::Keywords)
+}
+
+fn default_max_results_limit() -> Option<usize> {
+    Some(10)
+}
+
+fn default_min_score_floor() -> Option<f64> {
+    Some(0.1)
+}
+
 fn default_update_interval_secs() -> Option<u32> {
     Some(3600)
 }
 
+fn default_typo_weight() -> Option<f64> {
+    Some(0.5)
+}
+
+fn default_compound_splitting() -> Option<bool> {
+    Some(true)
+}
+
+fn default_compound_min_part_len() -> Option<usize> {
+    Some(3)
+}
+
+fn default_transliteration() -> Option<bool> {
+    Some(false)
+}
+
+fn default_numeric_match() -> Option<NumericMatch> {
+    Some(NumericMatch::Fuzzy)
+}
+
+fn default_spelling_correction() -> Option<bool> {
+    Some(true)
+}
+
+fn default_spelling_max_distance() -> Option<usize> {
+    Some(2)
+}
+
+fn default_spelling_threshold() -> Option<f64> {
+    Some(0.5)
+}
+
+fn default_suggestion_count() -> Option<usize> {
+    Some(5)
+}
+
+fn default_response_layout() -> Option<ResponseLayout> {
+    Some(ResponseLayout::Flat)
+}
+
+fn default_allow_explain() -> Option<bool> {
+    Some(false)
+}
+
+fn default_extraction_min_score() -> Option<f64> {
+    Some(0.5)
+}
+
+fn default_context_weight() -> Option<f64> {
+    Some(0.3)
+}
+
+fn default_see_also_max_depth() -> Option<usize> {
+    Some(1)
+}
+
+fn default_see_also_max_count() -> Option<usize> {
+    Some(3)
+}
+
+fn default_related_count() -> Option<usize> {
+    Some(3)
+}
+
+fn default_co_listing_min_count() -> Option<usize> {
+    Some(3)
+}
+
+fn default_topics_page_size() -> Option<usize> {
+    Some(20)
+}
+
+fn default_topic_keyword_count() -> Option<usize> {
+    Some(3)
+}
+
+fn default_confidence_bands() -> Option<ConfidenceBands> {
+    Some(ConfidenceBands::default())
+}
+
 // Generate all configuration boilerplate with one macro!
 declare_plugin_config!(PluginConfig);
 
-use crate::async_tasks::MorselEntry;
+use crate::async_tasks::{MorselEntry, MorselSimilarity};
 use env_logger::{Builder, Target};
 use log::{debug, error, info, warn};
 
@@ -112,65 +666,181 @@ fn init() -> Result<(), String> {
 fn handle_get_morsel(args: &Value) -> Result<Value, String> {
     debug!("zeno_keywords_to_morsel called with args: {args:?}");
     let config = get_config();
-    let keywords = args["keywords"]
-        .as_str()
-        .ok_or("Missing or invalid parameter 'keywords'")?;
-
-    let keywords = keywords
-        .split([',', ' '])
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<_>>();
+    // read before searching, a reload during the search invalidates the token
+    let version = DATABASE_VERSION.load(Ordering::SeqCst);
+    let continuation = match &args["continuation"] {
+        Value::Null => None,
+        Value::String(token) => Some(Continuation::decode(token, config)?),
+        _ => return Err("Invalid parameter 'continuation', expected a string".into()),
+    };
+    // extracted words outside the vocabulary are dropped unless they match well
+    let mut optional_keywords = Vec::new();
+    let mut extracted_keywords = Vec::new();
+    let mut keywords = match &continuation {
+        Some(continuation) => continuation.keywords.clone(),
+        None => match &args["question"] {
+            Value::Null => parse_keywords(&args["keywords"])?,
+            Value::String(question) => {
+                let mut keywords = match &args["keywords"] {
+                    Value::Null => vec![],
+                    keywords => parse_keywords(keywords)?,
+                };
+                for extracted in extract_keywords(question)? {
+                    if !keywords.contains(&extracted.keyword) {
+                        if !extracted.known {
+                            optional_keywords.push(extracted.keyword.clone());
+                        }
+                        extracted_keywords.push(extracted.keyword.clone());
+                        keywords.push(extracted.keyword);
+                    }
+                }
+                debug!("handle_get_morsel: extracted {extracted_keywords:?} from '{question}'");
+                keywords
+            }
+            _ => return Err("Invalid parameter 'question', expected a string".into()),
+        },
+    };
+    let searched_keywords = keywords.clone();
+    let mut dropped_keywords = Vec::new();
+    let options = SearchOptions::from_args(args, config, continuation.as_ref())?;
+    let mut has_more = false;
     let mut failed_keywords = Vec::new();
-    let mut matches = Vec::new();
+    let mut unmatched_keywords = Vec::new();
+    let mut corrections = Vec::new();
+    let mut keyword_matches = Vec::with_capacity(keywords.len());
     for keyword in &keywords {
-        let kwd_matches = MORSEL_TRIGRAMS
-            .read()
-            .map_err(|e| format!("cannot read directory entries: {e}"))?
-            .as_ref()
-            .ok_or("Morsel data is not initialized")?
-            .search(
-                keyword,
-                config.morsel_n_best.unwrap(),
-                config.morsel_min_score.unwrap(),
-            );
-        if kwd_matches.is_empty() && config.failed_keywords_path.is_some() {
-            failed_keywords.push(keyword.to_string());
+        let mut kwd_matches = search_keyword(keyword, &options, config)?;
+        if let Some(corrected) = correct_keyword(keyword, &kwd_matches, config)? {
+            let corrected_matches = search_keyword(&corrected, &options, config)?;
+            if best_score(&corrected_matches) > best_score(&kwd_matches) {
+                debug!("handle_get_morsel: corrected '{keyword}' to '{corrected}'");
+                kwd_matches = corrected_matches;
+                // the keyword as given differs from the corrected one, so no match is exact
+                kwd_matches.iter_mut().for_each(|m| {
+                    m.exact = false;
+                    annotate(m, format!("spelling corrected '{keyword}' to '{corrected}'"))
+                });
+                corrections.push(Correction {
+                    keyword: keyword.to_string(),
+                    corrected,
+                });
+            }
+        }
+        // attribute corrected and compound matches to the keyword as given
+        kwd_matches
+            .iter_mut()
+            .for_each(|m| m.query = keyword.to_string());
+        // filler words of a question must not pull in loosely matching morsels
+        if optional_keywords.contains(keyword) {
+            let min_score = config.extraction_min_score.unwrap().max(options.min_score);
+            kwd_matches.retain(|m| m.score >= min_score);
+        }
+        if kwd_matches.is_empty() && optional_keywords.contains(keyword) {
+            dropped_keywords.push(keyword.clone());
+            continue;
         }
-        matches.extend_from_slice(&kwd_matches);
+        if kwd_matches.is_empty() {
+            unmatched_keywords.push(keyword.to_string());
+            if config.failed_keywords_path.is_some() {
+                failed_keywords.push(keyword.to_string());
+            }
+        }
+        kwd_matches.retain(|m| !options.exclude_ids.contains(&m.item.id));
+        rerank_by_context(&mut kwd_matches, &options);
+        // a candidate beyond the limit means there is a next page
+        has_more |= kwd_matches.len() > options.per_keyword_n_best;
+        kwd_matches.truncate(options.per_keyword_n_best);
+        keyword_matches.push(kwd_matches);
     }
 
-    matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
-    if let Some(n_best) = config.morsel_n_best
-        && n_best < matches.len()
-    {
-        matches.truncate(n_best)
-    }
+    keywords.retain(|keyword| !dropped_keywords.contains(keyword));
+    extracted_keywords.retain(|keyword| !dropped_keywords.contains(keyword));
+
+    let (matches, truncated) = select_matches(keyword_matches, &options);
+    has_more |= truncated;
 
-    if !failed_keywords.is_empty() {
+    // failed keywords were logged with the first page
+    if !failed_keywords.is_empty() && continuation.is_none() {
         log_failed_keywords(&failed_keywords, config);
     }
 
-    let md_content = if !matches.is_empty() {
+    // a keyword whose matches were all excluded is covered, its morsels are known already
+    let any_matched = keywords
+        .iter()
+        .any(|keyword| !unmatched_keywords.contains(keyword));
+    let md_content = if any_matched {
+        let bands = config.confidence_bands.as_ref().unwrap();
         let mut morsels = Vec::with_capacity(matches.len());
-        matches.into_iter().for_each(|m| {
-            let item = m.item;
-
-            morsels.push(DbMorsel {
-                id: item.id.clone(),
-                content: item.content.clone(),
-                link: item.link.clone(),
-                score: m.score as f32,
-            });
-        });
-        ToolResponse::Success {
-            results_count: morsels.len(),
-            morsels,
+        matches
+            .into_iter()
+            .for_each(|m| morsels.push(DbMorsel::from_match(m).with_confidence(bands)));
+        if options.expand_see_also {
+            expand_see_also(&mut morsels, &options, config)?;
+        }
+        let guidance = bands.guidance(&morsels);
+        add_related_topics(&mut morsels, config)?;
+        record_hits(&morsels);
+        let next_page = if has_more {
+            let mut seen = options.exclude_ids.clone();
+            seen.extend(morsels.iter().map(|morsel| morsel.id.clone()));
+            Some(
+                Continuation {
+                    keywords: keywords.clone(),
+                    version,
+                    seen,
+                    per_keyword_n_best: options.per_keyword_n_best,
+                    max_total_results: options.max_total_results,
+                    min_score: options.min_score,
+                }
+                .encode()?,
+            )
+        } else {
+            None
+        };
+        if options.layout == ResponseLayout::Grouped {
+            ToolResponse::Grouped {
+                results_count: morsels.len(),
+                groups: group_morsels(&keywords, morsels, &unmatched_keywords, config)?,
+                guidance,
+                corrections,
+                extracted_keywords,
+                continuation: next_page,
+            }
+        } else if unmatched_keywords.is_empty() {
+            ToolResponse::Success {
+                results_count: morsels.len(),
+                morsels,
+                guidance,
+                corrections,
+                extracted_keywords,
+                continuation: next_page,
+            }
+        } else {
+            ToolResponse::PartialMatch {
+                results_count: morsels.len(),
+                morsels,
+                guidance,
+                unmatched_keywords: unmatched_keywords
+                    .into_iter()
+                    .map(|keyword| {
+                        Ok(UnmatchedKeyword {
+                            near_misses: near_misses(&keyword, config)?,
+                            keyword,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()?,
+                corrections,
+                extracted_keywords,
+                continuation: next_page,
+            }
         }
     } else {
         ToolResponse::NoMatch {
-            searched_keywords: keywords.iter().map(|s| s.to_string()).collect(),
-            suggestion: "Try searching for broader terms like 'security' or 'api'.".into(),
+            suggested_keywords: suggest_keywords(&searched_keywords, config)?,
+            searched_keywords,
+            corrections,
+            fallback: fallback_morsel(&options.exclude_ids, config)?,
+            escalation: config.escalation.clone(),
         }
     };
 
@@ -182,6 +852,746 @@ fn handle_get_morsel(args: &Value) -> Result<Value, String> {
     ))
 }
 
+/// Append the morsels linked by the see also links of the returned morsels, breadth first
+/// up to the configured depth and count. Linked morsels take the score and query keyword
+/// of the morsel linking to them, so they are grouped and banded with it.
+fn expand_see_also(
+    morsels: &mut Vec<DbMorsel>,
+    options: &SearchOptions,
+    config: &PluginConfig,
+) -> Result<(), String> {
+    let max_count = config.see_also_max_count.unwrap();
+    let trigrams = morsel_index()?;
+    let bands = config.confidence_bands.as_ref().unwrap();
+
+    let mut added = 0;
+    let mut frontier = (0..morsels.len()).collect::<Vec<_>>();
+    for _depth in 0..config.see_also_max_depth.unwrap() {
+        let mut next = Vec::new();
+        for parent in frontier {
+            let Some(entry) = trigrams.get(&morsels[parent].id) else {
+                continue;
+            };
+            for id in &entry.see_also {
+                if added >= max_count {
+                    return Ok(());
+                }
+                if morsels.iter().any(|morsel| &morsel.id == id)
+                    || options.exclude_ids.contains(id)
+                {
+                    continue;
+                }
+                let Some(item) = trigrams.get(id) else {
+                    continue;
+                };
+                let parent = &morsels[parent];
+                let linked = DbMorsel {
+                    query_keyword: parent.query_keyword.clone(),
+                    linked_from: Some(parent.id.clone()),
+                    ..DbMorsel::new(item, parent.score as f64)
+                }
+                .with_confidence(bands);
+                morsels.push(linked);
+                next.push(morsels.len() - 1);
+                added += 1;
+            }
+        }
+        frontier = next;
+    }
+    Ok(())
+}
+
+/// Related topics of every returned morsel: explicitly linked morsels first, then morsels
+/// frequently returned together with it, then morsels sharing keywords.
+/// Morsels of the response itself are not offered again.
+fn add_related_topics(morsels: &mut [DbMorsel], config: &PluginConfig) -> Result<(), String> {
+    if config.related_count.unwrap() == 0 {
+        return Ok(());
+    }
+    let trigrams = morsel_index()?;
+    let co_listings = MORSEL_CO_LISTINGS
+        .read()
+        .map_err(|e| format!("cannot read morsel co-listings: {e}"))?;
+    relate_morsels(morsels, &trigrams, &co_listings, config);
+    Ok(())
+}
+
+/// See 'add_related_topics', with the index and co-listing counts to derive the topics from
+fn relate_morsels(
+    morsels: &mut [DbMorsel],
+    trigrams: &Trigrams<MorselEntry>,
+    co_listings: &HashMap<String, HashMap<String, usize>>,
+    config: &PluginConfig,
+) {
+    let count = config.related_count.unwrap();
+    let min_co_listings = config.co_listing_min_count.unwrap();
+    let returned = morsels
+        .iter()
+        .map(|morsel| morsel.id.clone())
+        .collect::<HashSet<_>>();
+
+    for morsel in morsels.iter_mut() {
+        let Some(entry) = trigrams.get(&morsel.id) else {
+            continue;
+        };
+        // (reason, strength, id), sorted by reason, then by strength descending
+        let mut candidates: Vec<(RelatedReason, usize, &MorselEntry)> = Vec::new();
+        for (i, id) in entry.related.iter().enumerate() {
+            if let Some(item) = trigrams.get(id) {
+                candidates.push((RelatedReason::Linked, usize::MAX - i, item));
+            }
+        }
+        if let Some(counts) = co_listings.get(&entry.id) {
+            for (id, listings) in counts {
+                if *listings < min_co_listings {
+                    continue;
+                }
+                if let Some(item) = trigrams.get(id) {
+                    candidates.push((RelatedReason::CoListed, *listings, item));
+                }
+            }
+        }
+        for item in trigrams.items().filter(|item| item.id != entry.id) {
+            let shared = entry.shared_keywords(item);
+            if shared > 0 {
+                candidates.push((RelatedReason::SharedKeywords, shared, item));
+            }
+        }
+        candidates.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then_with(|| b.1.cmp(&a.1))
+                .then_with(|| a.2.id.cmp(&b.2.id))
+        });
+
+        for (reason, _strength, item) in candidates {
+            if morsel.related.len() >= count {
+                break;
+            }
+            if returned.contains(&item.id) || morsel.related.iter().any(|r| r.id == item.id) {
+                continue;
+            }
+            if let Some(keyword) = item.keywords.first() {
+                morsel.related.push(RelatedTopic {
+                    id: item.id.clone(),
+                    keyword: keyword.clone(),
+                    reason,
+                });
+            }
+        }
+    }
+}
+
+/// Fetch a morsel by its exact id, e.g. from a link, a see also reference or a previous
+/// result. Bypasses the fuzzy search, the score of the morsel is 1.
+fn handle_get_morsel_by_id(args: &Value) -> Result<Value, String> {
+    debug!("zeno_get_morsel_by_id called with args: {args:?}");
+    let config = get_config();
+    let id = match &args["id"] {
+        Value::String(id) => id.trim(),
+        Value::Null => return Err("Missing parameter 'id'".into()),
+        _ => return Err("Invalid parameter 'id', expected a string".into()),
+    };
+    let morsel = morsel_index()?
+        .get(id)
+        .map(|item| DbMorsel::new(item, 1.0))
+        .ok_or(format!(
+            "No morsel with id '{id}', use 'keywords_to_morsel' to search by keywords"
+        ))?;
+    let mut morsels = [morsel];
+    add_related_topics(&mut morsels, config)?;
+    let [morsel] = morsels;
+
+    debug!("handle_get_morsel_by_id: returning: {morsel:?}");
+    Ok(utils::json_content(
+        serde_json::to_value(morsel).map_err(|e| e.to_string())?,
+    ))
+}
+
+/// Topics covered by the database ordered by id, optionally filtered by category,
+/// so the LLM can discover the coverage before searching
+fn handle_list_topics(args: &Value) -> Result<Value, String> {
+    debug!("zeno_list_topics called with args: {args:?}");
+    let config = get_config();
+    let category = match &args["category"] {
+        Value::Null => None,
+        Value::String(category) => Some(category.trim().to_lowercase()),
+        _ => return Err("Invalid parameter 'category', expected a string".into()),
+    };
+    let offset = match &args["offset"] {
+        Value::Null => 0,
+        value => value
+            .as_u64()
+            .ok_or("Invalid parameter 'offset', expected a non-negative integer")?
+            as usize,
+    };
+    let page_size = config.topics_page_size.unwrap();
+    let keyword_count = config.topic_keyword_count.unwrap();
+
+    let trigrams = morsel_index()?;
+    let mut items = trigrams
+        .items()
+        .filter(|item| {
+            category.as_ref().is_none_or(|category| {
+                item.category
+                    .as_ref()
+                    .is_some_and(|c| c.to_lowercase() == *category)
+            })
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let total_count = items.len();
+    let topic_list = TopicList {
+        total_count,
+        topics: items
+            .into_iter()
+            .skip(offset)
+            .take(page_size)
+            .map(|item| Topic {
+                id: item.id.clone(),
+                title: item.title.clone(),
+                category: item.category.clone(),
+                keywords: item.keywords.iter().take(keyword_count).cloned().collect(),
+            })
+            .collect(),
+        next_offset: Some(offset.saturating_add(page_size)).filter(|next| *next < total_count),
+    };
+
+    debug!("handle_list_topics: returning: {topic_list:?}");
+    Ok(utils::json_content(
+        serde_json::to_value(topic_list).map_err(|e| e.to_string())?,
+    ))
+}
+
+/// One group per query keyword in query order. A morsel matched by several keywords
+/// is listed once, under the keyword it matched best.
+fn group_morsels(
+    keywords: &[String],
+    mut morsels: Vec<DbMorsel>,
+    unmatched_keywords: &[String],
+    config: &PluginConfig,
+) -> Result<Vec<KeywordGroup>, String> {
+    keywords
+        .iter()
+        .map(|keyword| {
+            let (group, rest) = std::mem::take(&mut morsels)
+                .into_iter()
+                .partition(|morsel| morsel.query_keyword.as_ref() == Some(keyword));
+            morsels = rest;
+            let (status, near_misses) = if unmatched_keywords.contains(keyword) {
+                (GroupStatus::NoMatch, near_misses(keyword, config)?)
+            } else if group.is_empty() {
+                (GroupStatus::NoNewMorsels, vec![])
+            } else {
+                (GroupStatus::Matched, vec![])
+            };
+            Ok(KeywordGroup {
+                keyword: keyword.clone(),
+                status,
+                morsels: group,
+                near_misses,
+            })
+        })
+        .collect()
+}
+
+/// Keywords of a natural language question, see 'KeywordExtractor'
+fn extract_keywords(question: &str) -> Result<Vec<ExtractedKeyword>, String> {
+    Ok(MORSEL_EXTRACTOR
+        .read()
+        .map_err(|e| format!("cannot read keyword extractor: {e}"))?
+        .as_ref()
+        .ok_or("Morsel data is not initialized")?
+        .extract(question))
+}
+
+/// Keywords are either a comma separated string or an array of phrases, which are not split
+fn parse_keywords(keywords: &Value) -> Result<Vec<String>, String> {
+    match keywords {
+        Value::String(keywords) => Ok(split_keywords(keywords)),
+        Value::Array(phrases) => phrases
+            .iter()
+            .map(|phrase| {
+                phrase.as_str().map(|s| s.trim().to_string()).ok_or(
+                    "Invalid parameter 'keywords', array elements must be strings".to_string(),
+                )
+            })
+            .filter(|phrase| phrase.as_ref().map_or(true, |s| !s.is_empty()))
+            .collect(),
+        Value::Null => Err("Missing parameter 'keywords' or 'question'".into()),
+        _ => Err("Invalid parameter 'keywords', expected a string or an array of strings".into()),
+    }
+}
+
+/// Morsel ids the LLM already has, together with those of previous pages
+fn parse_exclude_ids(
+    exclude_ids: &Value,
+    continuation: Option<&Continuation>,
+) -> Result<Vec<String>, String> {
+    let mut ids = continuation.map_or(vec![], |continuation| continuation.seen.clone());
+    match exclude_ids {
+        Value::Null => {}
+        Value::Array(values) => {
+            for value in values {
+                let id = value
+                    .as_str()
+                    .ok_or("Invalid parameter 'exclude_ids', array elements must be strings")?;
+                if !ids.iter().any(|seen| seen == id) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        _ => return Err("Invalid parameter 'exclude_ids', expected an array of strings".into()),
+    }
+    Ok(ids)
+}
+
+/// Search parameters of a single call: configured defaults with per-call overrides
+/// clamped to the configured bounds
+struct SearchOptions {
+    per_keyword_n_best: usize,
+    max_total_results: usize,
+    guarantee_per_keyword: bool,
+    min_score: f64,
+    mmr_lambda: Option<f64>,
+    mmr_similarity: MorselSimilarity,
+    explain: bool,
+    layout: ResponseLayout,
+    /// morsels returned on previous pages or already known to the LLM
+    exclude_ids: Vec<String>,
+    /// significant words of the user's message for re-ranking
+    context: Option<HashSet<String>>,
+    context_weight: f64,
+    expand_see_also: bool,
+}
+
+impl SearchOptions {
+    fn from_args(
+        args: &Value,
+        config: &PluginConfig,
+        continuation: Option<&Continuation>,
+    ) -> Result<Self, String> {
+        let n_best = config.morsel_n_best.unwrap();
+        let (per_keyword_n_best, max_total_results) = match &args["max_results"] {
+            // later pages keep the page size of the first
+            _ if let Some(continuation) = continuation => (
+                continuation.per_keyword_n_best,
+                continuation.max_total_results,
+            ),
+            Value::Null => (
+                config.per_keyword_n_best.unwrap_or(n_best),
+                config.max_total_results.unwrap_or(n_best),
+            ),
+            value => {
+                let max_results = (value
+                    .as_u64()
+                    .ok_or("Invalid parameter 'max_results', expected a positive integer")?
+                    as usize)
+                    // not clamp, which panics on a misconfigured limit of 0
+                    .min(config.max_results_limit.unwrap())
+                    .max(1);
+                (max_results, max_results)
+            }
+        };
+        let min_score = match &args["min_score"] {
+            _ if let Some(continuation) = continuation => continuation.min_score,
+            Value::Null => config.morsel_min_score.unwrap(),
+            value => value
+                .as_f64()
+                .ok_or("Invalid parameter 'min_score', expected a number between 0 and 1")?
+                .max(config.min_score_floor.unwrap())
+                .min(1.0),
+        };
+        Ok(Self {
+            per_keyword_n_best,
+            max_total_results,
+            // every matched keyword gets a non-empty group
+            guarantee_per_keyword: config.guarantee_per_keyword.unwrap()
+                || config.response_layout.unwrap() == ResponseLayout::Grouped,
+            min_score,
+            mmr_lambda: config.mmr_lambda,
+            mmr_similarity: config.mmr_similarity.unwrap(),
+            explain: match &args["explain"] {
+                Value::Null => false,
+                value => {
+                    let explain = value
+                        .as_bool()
+                        .ok_or("Invalid parameter 'explain', expected a boolean")?;
+                    if explain && !config.allow_explain.unwrap() {
+                        warn!("explain requested but not allowed by configuration");
+                    }
+                    explain && config.allow_explain.unwrap()
+                }
+            },
+            layout: config.response_layout.unwrap(),
+            exclude_ids: parse_exclude_ids(&args["exclude_ids"], continuation)?,
+            context: match &args["context"] {
+                Value::Null => None,
+                Value::String(context) => Some(significant_words(context))
+                    .filter(|words| !words.is_empty() && config.context_weight.unwrap() > 0.0),
+                _ => return Err("Invalid parameter 'context', expected a string".into()),
+            },
+            context_weight: config.context_weight.unwrap(),
+            expand_see_also: match &args["expand_see_also"] {
+                Value::Null => false,
+                value => value
+                    .as_bool()
+                    .ok_or("Invalid parameter 'expand_see_also', expected a boolean")?,
+            },
+        })
+    }
+}
+
+/// Merge the matches of all query keywords, keeping the best score of a morsel matched
+/// by several keywords, order them by 'rank_score' and limit them to the maximum number
+/// of total results.
+/// If results are guaranteed per keyword, the best morsel of every matched keyword is
+/// included even if that exceeds the total limit.
+/// With maximal marginal relevance enabled, morsels are selected and ordered by their
+/// MMR rank instead of their score.
+/// Returns the selection and whether matches were left out due to the total limit.
+fn select_matches(
+    keyword_matches: Vec<Vec<Match<MorselEntry>>>,
+    options: &SearchOptions,
+) -> (Vec<Match<MorselEntry>>, bool) {
+    let mut guaranteed: Vec<String> = Vec::new();
+    if options.guarantee_per_keyword {
+        for kwd_matches in &keyword_matches {
+            if let Some(best) = kwd_matches
+                .iter()
+                .find(|m| !guaranteed.contains(&m.item.id))
+            {
+                guaranteed.push(best.item.id.clone());
+            }
+        }
+    }
+
+    let mut merged: Vec<Match<MorselEntry>> = Vec::new();
+    for m in keyword_matches.into_iter().flatten() {
+        match merged.iter_mut().find(|existing| existing.item.id == m.item.id) {
+            Some(existing) if existing.score < m.score => *existing = m,
+            Some(_) => {}
+            None => merged.push(m),
+        }
+    }
+    sort_by_rank(&mut merged, options);
+    if let Some(lambda) = options.mmr_lambda {
+        merged = mmr_rerank(merged, lambda, options.mmr_similarity);
+        merged
+            .iter_mut()
+            .enumerate()
+            .for_each(|(rank, m)| annotate(m, format!("mmr rank {rank}, lambda {lambda}")));
+    }
+
+    // a guaranteed morsel is reported with its best match, which may be of another keyword
+    let mut selected = merged
+        .iter()
+        .filter(|m| guaranteed.contains(&m.item.id))
+        .cloned()
+        .collect::<Vec<_>>();
+    selected
+        .iter_mut()
+        .for_each(|s| annotate(s, "guaranteed result of its keyword".to_string()));
+    let rank = merged
+        .iter()
+        .map(|m| m.item.id.clone())
+        .collect::<Vec<_>>();
+    let mut truncated = false;
+    for m in merged {
+        if selected.iter().any(|s| s.item.id == m.item.id) {
+            continue;
+        }
+        if selected.len() >= options.max_total_results {
+            truncated = true;
+            break;
+        }
+        selected.push(m);
+    }
+    if options.mmr_lambda.is_some() {
+        selected.sort_by_key(|s| rank.iter().position(|id| *id == s.item.id));
+    } else {
+        sort_by_rank(&mut selected, options);
+    }
+    (selected, truncated)
+}
+
+/// Order the matches by their rank score, so among similar matches a conversation about
+/// GDPR prefers the GDPR morsel. The match scores are kept.
+fn rerank_by_context(matches: &mut [Match<MorselEntry>], options: &SearchOptions) {
+    let Some(context) = &options.context else {
+        return;
+    };
+    for m in matches.iter_mut() {
+        if let Some(explanation) = &mut m.explanation {
+            let overlap = m.item.context_overlap(context);
+            explanation
+                .components
+                .insert("context_overlap".to_string(), overlap);
+        }
+        annotate(m, format!("context re-ranking, weight {}", options.context_weight));
+    }
+    sort_by_rank(matches, options);
+}
+
+/// Score the matches are ordered by: the match score blended with the overlap between the
+/// user's message and the morsel, or the match score alone without a context
+fn rank_score(m: &Match<MorselEntry>, options: &SearchOptions) -> f64 {
+    match &options.context {
+        Some(context) => {
+            let weight = options.context_weight;
+            (1.0 - weight) * m.score + weight * m.item.context_overlap(context)
+        }
+        None => m.score,
+    }
+}
+
+fn sort_by_rank(matches: &mut [Match<MorselEntry>], options: &SearchOptions) {
+    matches.sort_by(|a, b| {
+        rank_score(b, options)
+            .partial_cmp(&rank_score(a, options))
+            .unwrap()
+    });
+}
+
+/// Maximal marginal relevance: greedily pick the candidate maximizing
+/// `lambda * score - (1 - lambda) * max similarity to the already picked candidates`,
+/// so near duplicates of a better morsel move down the list
+fn mmr_rerank(
+    mut candidates: Vec<Match<MorselEntry>>,
+    lambda: f64,
+    similarity: MorselSimilarity,
+) -> Vec<Match<MorselEntry>> {
+    let mut ranked: Vec<Match<MorselEntry>> = Vec::with_capacity(candidates.len());
+    while !candidates.is_empty() {
+        let mmr = |candidate: &Match<MorselEntry>| {
+            let redundancy = ranked
+                .iter()
+                .map(|r| candidate.item.similarity(&r.item, similarity))
+                .fold(0.0, f64::max);
+            lambda * candidate.score - (1.0 - lambda) * redundancy
+        };
+        let best = candidates
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| mmr(a).partial_cmp(&mmr(b)).unwrap())
+            .map(|(i, _)| i)
+            .expect("candidates are not empty");
+        ranked.push(candidates.remove(best));
+    }
+    ranked
+}
+
+/// Fuzzy search for a single keyword. Compound keywords are additionally decomposed
+/// into their parts, the part scores are fused weighted by part length.
+fn search_keyword(
+    keyword: &str,
+    options: &SearchOptions,
+    config: &PluginConfig,
+) -> Result<Vec<Match<MorselEntry>>, String> {
+    // excluded morsels are removed afterwards, one more reveals a next page
+    let mut n_best = options
+        .per_keyword_n_best
+        .saturating_add(options.exclude_ids.len())
+        .saturating_add(1);
+    if options.context.is_some() {
+        // the context may promote candidates below the per keyword limit
+        n_best = n_best.max(
+            config
+                .max_results_limit
+                .unwrap()
+                .saturating_add(options.exclude_ids.len())
+                .saturating_add(1),
+        );
+    }
+    let min_score = options.min_score;
+    let trigrams = morsel_index()?;
+    let search = |query: &str| {
+        if options.explain {
+            trigrams.search_explained(query, n_best, min_score)
+        } else {
+            trigrams.search(query, n_best, min_score)
+        }
+    };
+    let mut kwd_matches = search(keyword);
+
+    let parts = if config.compound_splitting.unwrap() {
+        MORSEL_COMPOUNDS
+            .read()
+            .map_err(|e| format!("cannot read compound vocabulary: {e}"))?
+            .as_ref()
+            .and_then(|compounds| compounds.split(keyword))
+    } else {
+        None
+    };
+
+    if let Some(parts) = parts {
+        debug!("search_keyword: decomposed '{keyword}' into {parts:?}");
+        let total_len = parts.iter().map(|part| part.chars().count()).sum::<usize>() as f64;
+        let mut fused: Vec<Match<MorselEntry>> = Vec::new();
+        for part in &parts {
+            let weight = part.chars().count() as f64 / total_len;
+            for mut part_match in search(part) {
+                annotate(
+                    &mut part_match,
+                    format!("compound part '{part}' of '{keyword}', weight {weight:.2}"),
+                );
+                match fused.iter_mut().find(|m| m.item.id == part_match.item.id) {
+                    Some(existing) => {
+                        existing.score += weight * part_match.score;
+                        if let (Some(explanation), Some(part_explanation)) =
+                            (&mut existing.explanation, part_match.explanation)
+                        {
+                            explanation.filters.extend(part_explanation.filters);
+                        }
+                    }
+                    None => fused.push(Match {
+                        score: weight * part_match.score,
+                        exact: false,
+                        ..part_match
+                    }),
+                }
+            }
+        }
+
+        for mut fused_match in fused.into_iter().filter(|m| m.score >= min_score) {
+            if let Some(explanation) = &mut fused_match.explanation {
+                explanation
+                    .components
+                    .insert("fused_score".to_string(), fused_match.score);
+            }
+            match kwd_matches.iter_mut().find(|m| m.item.id == fused_match.item.id) {
+                Some(existing) if existing.score < fused_match.score => *existing = fused_match,
+                Some(_) => {}
+                None => kwd_matches.push(fused_match),
+            }
+        }
+        kwd_matches.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        kwd_matches.truncate(n_best);
+    }
+
+    Ok(kwd_matches)
+}
+
+/// Record a filter or transformation in the explanation of a match
+fn annotate(m: &mut Match<MorselEntry>, filter: String) {
+    if let Some(explanation) = &mut m.explanation {
+        explanation.filters.push(filter);
+    }
+}
+
+/// Spelling correction for a keyword whose best match scores below the spelling threshold
+fn correct_keyword(
+    keyword: &str,
+    kwd_matches: &[Match<MorselEntry>],
+    config: &PluginConfig,
+) -> Result<Option<String>, String> {
+    if !config.spelling_correction.unwrap()
+        || best_score(kwd_matches) >= config.spelling_threshold.unwrap()
+    {
+        return Ok(None);
+    }
+    Ok(MORSEL_SPELLING
+        .read()
+        .map_err(|e| format!("cannot read spelling dictionary: {e}"))?
+        .as_ref()
+        .and_then(|spelling| spelling.correct(keyword)))
+}
+
+/// Suggestions for a query without matches: the database keywords nearest to the
+/// searched keywords, followed by the keywords of the most frequently returned morsels
+fn suggest_keywords(keywords: &[String], config: &PluginConfig) -> Result<Vec<String>, String> {
+    let count = config.suggestion_count.unwrap();
+    let trigrams = morsel_index()?;
+
+    let mut suggestions = Vec::with_capacity(count);
+    for keyword in keywords {
+        for nearest in trigrams.nearest_keywords(keyword, count) {
+            if !suggestions.contains(&nearest) {
+                suggestions.push(nearest);
+            }
+        }
+    }
+    // leave room for at least one topic
+    suggestions.truncate(count.saturating_sub(1).max(1));
+
+    let hits = MORSEL_HITS
+        .read()
+        .map_err(|e| format!("cannot read morsel hits: {e}"))?;
+    let mut topics = trigrams
+        .items()
+        .filter_map(|item| {
+            item.keywords
+                .first()
+                .map(|keyword| (hits.get(&item.id).copied().unwrap_or(0), &item.id, keyword))
+        })
+        .collect::<Vec<_>>();
+    // most hits first, then by id for a stable order
+    topics.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    for (_hits, _id, keyword) in topics {
+        if suggestions.len() >= count {
+            break;
+        }
+        if !suggestions.contains(keyword) {
+            suggestions.push(keyword.clone());
+        }
+    }
+
+    Ok(suggestions)
+}
+
+/// Database keywords nearest to a keyword without matches
+fn near_misses(keyword: &str, config: &PluginConfig) -> Result<Vec<String>, String> {
+    Ok(morsel_index()?.nearest_keywords(keyword, config.suggestion_count.unwrap()))
+}
+
+/// The configured fallback morsel unless it is excluded
+fn fallback_morsel(
+    exclude_ids: &[String],
+    config: &PluginConfig,
+) -> Result<Option<Box<DbMorsel>>, String> {
+    let Some(fallback_id) = &config.fallback_morsel_id else {
+        return Ok(None);
+    };
+    if exclude_ids.contains(fallback_id) {
+        return Ok(None);
+    }
+    let fallback = morsel_index()?
+        .get(fallback_id)
+        .map(|item| Box::new(DbMorsel::new(item, 0.0)));
+    if fallback.is_none() {
+        warn!("fallback morsel '{fallback_id}' not found in database");
+    }
+    Ok(fallback)
+}
+
+fn record_hits(morsels: &[DbMorsel]) {
+    match MORSEL_HITS.write() {
+        Ok(mut hits) => morsels
+            .iter()
+            .for_each(|morsel| *hits.entry(morsel.id.clone()).or_default() += 1),
+        Err(e) => error!("cannot access morsel hits: {e}"),
+    }
+    match MORSEL_CO_LISTINGS.write() {
+        Ok(mut co_listings) => {
+            for morsel in morsels {
+                let counts = co_listings.entry(morsel.id.clone()).or_default();
+                morsels
+                    .iter()
+                    .filter(|other| other.id != morsel.id)
+                    .for_each(|other| {
+                        let listings = counts.entry(other.id.clone()).or_default();
+                        *listings = listings.saturating_add(1);
+                    });
+            }
+        }
+        Err(e) => error!("cannot access morsel co-listings: {e}"),
+    }
+}
+
+fn best_score(matches: &[Match<MorselEntry>]) -> f64 {
+    matches.iter().map(|m| m.score).fold(0.0, f64::max)
+}
+
 pub fn log_failed_keywords(keywords: &[String], config: &PluginConfig) {
     if keywords.is_empty() {
         return;
@@ -219,6 +1629,7 @@ pub fn log_failed_keywords(keywords: &[String], config: &PluginConfig) {
             .read(true) // Required for Windows locking
             .write(true) // Atomic pointer positioning
             .create(true) // Create if missing
+            .truncate(false) // Truncated once the lock is held
             .open(path)
         {
             Ok(file) => file,
@@ -255,6 +1666,9 @@ pub fn log_failed_keywords(keywords: &[String], config: &PluginConfig) {
                         return;
                     }
                 };
+                let _ = file
+                    .set_len(0)
+                    .inspect_err(|e| error!("cannot truncate file: {e}"));
                 let _ = file
                     .write_all(data.as_bytes())
                     .inspect_err(|e| error!("cannot write to file: {e}"));
@@ -294,21 +1708,535 @@ fn  get_tool_description<'a>() -> &'a str {
     }
 }
 
+/// The explain parameter is only declared if explain mode is allowed by the configuration
+fn with_explain_param(builder: ToolBuilder) -> ToolBuilder {
+    if try_get_config().is_some_and(|config| config.allow_explain.unwrap()) {
+        builder.param_bool(
+            "explain",
+            "Debug mode: return for each result how its score came about",
+            false,
+        )
+    } else {
+        builder
+    }
+}
+
 declare_tools! {
     tools: [
-        Tool::builder("keywords_to_morsel",get_tool_description())
-            .param_string("keywords", "Comma separated list of keywords", true)
+        with_explain_param(Tool::builder("keywords_to_morsel",get_tool_description())
+            .param_string("keywords", "Comma separated list of keywords or an array of keyword phrases, required unless 'question' is given", false)
+            .param_string("question", "The user's question in natural language, keywords are extracted from it", false)
+            .param_i64("max_results", "Maximum number of morsels to return, e.g. more for exploratory questions", false)
+            .param_f64("min_score", "Minimum match score between 0 and 1, lower values return broader results", false)
+            .param_string("continuation", "Token from a previous response to fetch its next page of results, the keywords of that search are reused", false)
+            .param_array("exclude_ids", "Ids of morsels already known from this conversation, other morsels are returned instead", false)
+            .param_string("context", "The user's last message, used to prefer morsels related to the conversation", false)
+            .param_bool("expand_see_also", "Also return the morsels linked by the returned morsels, e.g. prerequisites", false))
             .handler(handle_get_morsel),
+        Tool::builder("get_morsel_by_id", "Fetch a morsel by its exact id, e.g. an id from a previous result, a 'related' topic or a 'linked_from' reference")
+            .param_string("id", "Id of the morsel", true)
+            .handler(handle_get_morsel_by_id),
+        Tool::builder("list_topics", "List the topics covered by the knowledge base with their ids, titles and representative keywords, to find good keywords before searching")
+            .param_string("category", "Only list topics of this category", false)
+            .param_i64("offset", "Number of topics to skip, use 'next_offset' of the previous page", false)
+            .handler(handle_list_topics),
     ]
 }
+
+/// list_tools with the 'keywords' parameter declared as a string or an array of strings
+/// and the item type of 'exclude_ids', which the tool builder cannot express
+unsafe extern "C" fn list_tools(result_buf: *mut *mut u8, result_len: *mut usize) -> i32 {
+    let tools = get_tools()
+        .values()
+        .map(|tool| {
+            let mut schema = tool.to_json_schema();
+            if let Some(keywords) = schema.pointer_mut("/inputSchema/properties/keywords") {
+                *keywords = json!({
+                    "description": keywords["description"],
+                    "anyOf": [
+                        { "type": "string" },
+                        { "type": "array", "items": { "type": "string" } }
+                    ]
+                });
+            }
+            if let Some(exclude_ids) = schema.pointer_mut("/inputSchema/properties/exclude_ids") {
+                exclude_ids["items"] = json!({ "type": "string" });
+            }
+            schema
+        })
+        .collect();
+    unsafe { utils::return_success(Value::Array(tools), result_buf, result_len) }
+}
+
 declare_config_schema!(PluginConfig);
 declare_plugin_init!(init);
 // Declare the plugin with auto-generated functions and configuration
 declare_plugin! {
-    list_tools: generated_list_tools,
+    list_tools: list_tools,
     execute_tool: generated_execute_tool,
     free_string: utils::standard_free_string,
     configure: plugin_configure,
     init: plugin_init,
     get_config_schema: plugin_get_config_schema
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sample database of the README with a category for every morsel and more see also links
+    const DATABASE: &str = "
+- id: security_overview
+  title: Security overview
+  category: security
+  keywords: [ security, encryption, safe, protected, protocol ]
+  link: /security
+  see_also: [ gdpr_compliance ]
+  content: All chat data is encrypted at rest using AES-256 and in transit via TLS 1.3.
+- id: gdpr_compliance
+  category: privacy
+  keywords: [ gdpr, privacy, data, europe, compliance, dpa ]
+  link: /privacy
+  related: [ data_retention ]
+  see_also: [ data_retention, security_overview ]
+  content: Zeno is fully GDPR compliant. Users can request data deletion at any time.
+- id: data_retention
+  category: Privacy
+  keywords: [ retention, storage, history, delete, logs ]
+  link: /docs/retention
+  content: By default, chat history is stored for 30 days.
+";
+
+    fn config() -> PluginConfig {
+        serde_json::from_value(json!({
+            "function_description": "test",
+            "database_path": "morsels.yaml",
+            "topics_page_size": 2,
+            "see_also_max_depth": 2,
+            "fallback_morsel_id": "security_overview",
+        }))
+        .unwrap()
+    }
+
+    /// Configures the plugin and loads 'DATABASE' once for all handler tests
+    fn load_database() -> &'static PluginConfig {
+        static LOADED: std::sync::Once = std::sync::Once::new();
+        LOADED.call_once(|| {
+            __PLUGIN_CONFIG.set(config()).unwrap();
+            let entries: Vec<MorselEntry> = serde_yaml::from_str(DATABASE).unwrap();
+            let max_distance = get_config().spelling_max_distance.unwrap();
+            let spelling = SpellingDictionary::new(&entries, max_distance);
+            *MORSEL_SPELLING.write().unwrap() = Some(spelling);
+            let trigrams = Trigrams::new(entries).unwrap();
+            let extractor = KeywordExtractor::new(trigrams.vocabulary());
+            *MORSEL_EXTRACTOR.write().unwrap() = Some(extractor);
+            *MORSEL_TRIGRAMS.write().unwrap() = Some(trigrams);
+        });
+        get_config()
+    }
+
+    /// JSON payload of a handler response
+    fn payload(response: Result<Value, String>) -> Value {
+        response.unwrap()["content"][0]["json"].clone()
+    }
+
+    fn ids(morsels: &Value) -> Vec<&str> {
+        morsels
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|morsel| morsel["id"].as_str().unwrap())
+            .collect()
+    }
+
+    fn continuation(per_keyword_n_best: usize, min_score: f64) -> Continuation {
+        Continuation {
+            keywords: vec!["security".to_string(), "tls 1.3".to_string()],
+            version: DATABASE_VERSION.load(Ordering::SeqCst),
+            seen: vec!["security_overview".to_string()],
+            per_keyword_n_best,
+            max_total_results: 3,
+            min_score,
+        }
+    }
+
+    #[test]
+    fn continuation_round_trip() {
+        let token = continuation(2, 0.3).encode().unwrap();
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        let decoded = Continuation::decode(&token, &config()).unwrap();
+        assert_eq!(decoded.keywords, vec!["security", "tls 1.3"]);
+        assert_eq!(decoded.seen, vec!["security_overview"]);
+        assert_eq!(decoded.per_keyword_n_best, 2);
+        assert_eq!(decoded.max_total_results, 3);
+        assert_eq!(decoded.min_score, 0.3);
+    }
+
+    #[test]
+    fn forged_continuation_is_bounded() {
+        let token = continuation(usize::MAX, 0.0).encode().unwrap();
+        let decoded = Continuation::decode(&token, &config()).unwrap();
+        assert_eq!(decoded.per_keyword_n_best, 10);
+        assert_eq!(decoded.min_score, 0.1);
+    }
+
+    #[test]
+    fn invalid_continuation() {
+        assert!(Continuation::decode("not a token", &config()).is_err());
+        assert!(Continuation::decode("7b7d", &config()).is_err());
+        let mut expired = continuation(1, 0.3);
+        expired.version += 1;
+        let error = Continuation::decode(&expired.encode().unwrap(), &config()).unwrap_err();
+        assert!(error.contains("expired"));
+    }
+
+    #[test]
+    fn list_topics_pages() {
+        load_database();
+        let first = payload(handle_list_topics(&json!({})));
+        assert_eq!(first["total_count"], 3);
+        assert_eq!(ids(&first["topics"]), vec!["data_retention", "gdpr_compliance"]);
+        assert_eq!(first["topics"][0]["keywords"], json!(["retention", "storage", "history"]));
+        assert_eq!(first["next_offset"], 2);
+
+        let last = payload(handle_list_topics(&json!({"offset": 2})));
+        assert_eq!(ids(&last["topics"]), vec!["security_overview"]);
+        assert_eq!(last["topics"][0]["title"], "Security overview");
+        assert!(last.get("next_offset").is_none());
+
+        let beyond = payload(handle_list_topics(&json!({"offset": u64::MAX})));
+        assert_eq!(beyond["total_count"], 3);
+        assert!(beyond["topics"].as_array().unwrap().is_empty());
+        assert!(beyond.get("next_offset").is_none());
+        assert!(handle_list_topics(&json!({"offset": -1})).is_err());
+    }
+
+    #[test]
+    fn list_topics_by_category() {
+        load_database();
+        let privacy = payload(handle_list_topics(&json!({"category": " privacy "})));
+        assert_eq!(privacy["total_count"], 2);
+        assert_eq!(ids(&privacy["topics"]), vec!["data_retention", "gdpr_compliance"]);
+        assert!(privacy.get("next_offset").is_none());
+
+        let unknown = payload(handle_list_topics(&json!({"category": "pricing"})));
+        assert_eq!(unknown["total_count"], 0);
+        assert!(handle_list_topics(&json!({"category": 1})).is_err());
+    }
+
+    fn entry(id: &str) -> MorselEntry {
+        let entries: Vec<MorselEntry> = serde_yaml::from_str(DATABASE).unwrap();
+        entries.into_iter().find(|entry| entry.id == id).unwrap()
+    }
+
+    fn matched(id: &str, query: &str, keyword: &str, score: f64) -> Match<MorselEntry> {
+        Match {
+            item: entry(id),
+            score,
+            query: query.to_string(),
+            keyword: keyword.to_string(),
+            exact: query == keyword,
+            explanation: None,
+        }
+    }
+
+    fn options(args: Value, guarantee_per_keyword: bool) -> SearchOptions {
+        let mut options = SearchOptions::from_args(&args, &config(), None).unwrap();
+        options.guarantee_per_keyword = guarantee_per_keyword;
+        options
+    }
+
+    fn summary(matches: &[Match<MorselEntry>]) -> Vec<(&str, &str, f64)> {
+        matches
+            .iter()
+            .map(|m| (m.item.id.as_str(), m.query.as_str(), m.score))
+            .collect()
+    }
+
+    #[test]
+    fn select_matches_merges_and_limits() {
+        let keyword_matches = vec![
+            vec![
+                matched("gdpr_compliance", "dat", "data", 0.4),
+                matched("data_retention", "dat", "delete", 0.3),
+            ],
+            vec![matched("gdpr_compliance", "privacy", "privacy", 1.0)],
+            vec![matched("security_overview", "safe", "safe", 1.0)],
+        ];
+        let (selected, truncated) =
+            select_matches(keyword_matches.clone(), &options(json!({"max_results": 2}), false));
+        assert_eq!(
+            summary(&selected),
+            vec![("gdpr_compliance", "privacy", 1.0), ("security_overview", "safe", 1.0)]
+        );
+        assert!(truncated);
+
+        let (selected, truncated) =
+            select_matches(keyword_matches, &options(json!({"max_results": 3}), false));
+        assert_eq!(selected.len(), 3);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn select_matches_guarantees_every_keyword() {
+        let keyword_matches = vec![
+            vec![
+                matched("gdpr_compliance", "dat", "data", 0.4),
+                matched("data_retention", "dat", "delete", 0.3),
+            ],
+            vec![matched("gdpr_compliance", "privacy", "privacy", 1.0)],
+            vec![matched("security_overview", "safe", "safe", 0.5)],
+        ];
+        let (selected, truncated) =
+            select_matches(keyword_matches, &options(json!({"max_results": 1}), true));
+        // gdpr_compliance is the best morsel of "dat" and reported with its best match
+        assert_eq!(
+            summary(&selected),
+            vec![("gdpr_compliance", "privacy", 1.0), ("security_overview", "safe", 0.5)]
+        );
+        assert_eq!(selected[0].keyword, "privacy");
+        assert!(selected[0].exact);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn groups_morsels_under_their_best_keyword() {
+        let config = load_database();
+        let keywords = ["dat", "privacy", "safe", "pricing"].map(String::from);
+        let keyword_matches = vec![
+            vec![matched("gdpr_compliance", "dat", "data", 0.4)],
+            vec![matched("gdpr_compliance", "privacy", "privacy", 1.0)],
+            vec![matched("security_overview", "safe", "safe", 1.0)],
+            vec![],
+        ];
+        let (selected, _) = select_matches(keyword_matches, &options(json!({}), true));
+        let morsels = selected.into_iter().map(DbMorsel::from_match).collect();
+        let groups =
+            group_morsels(&keywords, morsels, &["pricing".to_string()], config).unwrap();
+        let groups = serde_json::to_value(groups).unwrap();
+
+        assert_eq!(groups[0]["keyword"], "dat");
+        assert_eq!(groups[0]["status"], "no_new_morsels");
+        assert_eq!(groups[1]["status"], "matched");
+        assert_eq!(ids(&groups[1]["morsels"]), vec!["gdpr_compliance"]);
+        assert_eq!(groups[1]["morsels"][0]["matched_keyword"], "privacy");
+        assert_eq!(ids(&groups[2]["morsels"]), vec!["security_overview"]);
+        assert_eq!(groups[3]["status"], "no_match");
+        assert!(groups[3]["morsels"].as_array().unwrap().is_empty());
+        assert!(groups.as_array().unwrap().iter().all(|group| {
+            (group["status"] == "matched") != group["morsels"].as_array().unwrap().is_empty()
+        }));
+    }
+
+    #[test]
+    fn excluded_matches_still_count_as_matched() {
+        load_database();
+        let args = json!({"keywords": "security", "exclude_ids": ["security_overview"]});
+        let response = payload(handle_get_morsel(&args));
+        assert_eq!(response["status"], "success");
+        assert_eq!(response["results_count"], 0);
+        assert!(response["morsels"].as_array().unwrap().is_empty());
+
+        let args = json!({"keywords": "security, gdpr", "exclude_ids": ["security_overview"]});
+        let response = payload(handle_get_morsel(&args));
+        assert_eq!(response["status"], "success");
+        assert_eq!(ids(&response["morsels"]), vec!["gdpr_compliance"]);
+
+        let response = payload(handle_get_morsel(&json!({"keywords": "xylophone"})));
+        assert_eq!(response["status"], "no_match");
+        assert_eq!(response["fallback"]["id"], "security_overview");
+        let args = json!({"keywords": "xylophone", "exclude_ids": ["security_overview"]});
+        let response = payload(handle_get_morsel(&args));
+        assert_eq!(response["status"], "no_match");
+        assert!(response.get("fallback").is_none());
+    }
+
+    #[test]
+    fn context_reorders_without_changing_scores() {
+        let context = "we are in europe and want gdpr compliance";
+        let options = options(json!({"context": context}), false);
+        let mut matches = vec![
+            matched("data_retention", "delet", "delete", 0.5),
+            matched("gdpr_compliance", "delet", "data", 0.4),
+        ];
+        rerank_by_context(&mut matches, &options);
+        assert_eq!(
+            summary(&matches),
+            vec![("gdpr_compliance", "delet", 0.4), ("data_retention", "delet", 0.5)]
+        );
+        assert!(rank_score(&matches[0], &options) > rank_score(&matches[1], &options));
+
+        load_database();
+        let args = json!({"keywords": "delete", "context": context});
+        let response = payload(handle_get_morsel(&args));
+        assert_eq!(response["morsels"][0]["id"], "data_retention");
+        assert_eq!(response["morsels"][0]["score"], 1.0);
+        assert_eq!(response["morsels"][0]["confidence"], "high");
+    }
+
+    #[test]
+    fn unknown_question_words_need_a_good_match() {
+        load_database();
+        let question = json!({"question": "How long is my chat history kept?"});
+        let response = payload(handle_get_morsel(&question));
+        assert_eq!(response["status"], "success");
+        assert_eq!(response["extracted_keywords"], json!(["history"]));
+        assert_eq!(ids(&response["morsels"]), vec!["data_retention"]);
+
+        // misspelled words outside the vocabulary still match
+        let question = json!({"question": "How good is the secruity?"});
+        let response = payload(handle_get_morsel(&question));
+        assert_eq!(response["extracted_keywords"], json!(["secruity"]));
+        assert_eq!(ids(&response["morsels"]), vec!["security_overview"]);
+    }
+
+    fn related(morsel: &DbMorsel) -> Vec<(&str, RelatedReason)> {
+        morsel
+            .related
+            .iter()
+            .map(|topic| (topic.id.as_str(), topic.reason))
+            .collect()
+    }
+
+    #[test]
+    fn related_topics_by_reason() {
+        let config = config();
+        let entries: Vec<MorselEntry> = serde_yaml::from_str(DATABASE).unwrap();
+        let trigrams = Trigrams::new(entries).unwrap();
+        let morsel = |id: &str| DbMorsel::new(trigrams.get(id).unwrap(), 1.0);
+        let co_listed = |count: usize| {
+            HashMap::from([(
+                "security_overview".to_string(),
+                HashMap::from([("data_retention".to_string(), count)]),
+            )])
+        };
+
+        let mut morsels = [morsel("gdpr_compliance"), morsel("security_overview")];
+        relate_morsels(&mut morsels, &trigrams, &co_listed(1), &config);
+        assert_eq!(related(&morsels[0]), vec![("data_retention", RelatedReason::Linked)]);
+        // a single co-listing is no frequent co-listing
+        assert!(morsels[1].related.is_empty());
+
+        let mut morsels = [morsel("security_overview")];
+        relate_morsels(&mut morsels, &trigrams, &co_listed(3), &config);
+        assert_eq!(related(&morsels[0]), vec![("data_retention", RelatedReason::CoListed)]);
+
+        // morsels of the response are not offered again
+        let mut morsels = [morsel("gdpr_compliance"), morsel("data_retention")];
+        relate_morsels(&mut morsels, &trigrams, &co_listed(3), &config);
+        assert!(morsels.iter().all(|morsel| morsel.related.is_empty()));
+    }
+
+    #[test]
+    fn suggestions_start_with_the_nearest_keywords() {
+        let config = load_database();
+        let suggestions = suggest_keywords(&["secuirty".to_string()], config).unwrap();
+        assert_eq!(suggestions[0], "security");
+        assert_eq!(suggestions.len(), config.suggestion_count.unwrap());
+        let unique = suggestions.iter().collect::<HashSet<_>>();
+        assert_eq!(unique.len(), suggestions.len());
+        // the last slot is left for a topic, the first keyword of a morsel
+        let topics = ["security", "gdpr", "retention"];
+        assert!(topics.contains(&suggestions.last().unwrap().as_str()));
+    }
+
+    #[test]
+    fn keywords_as_string_or_phrases() {
+        assert_eq!(
+            parse_keywords(&json!("security, tls 1.3")).unwrap(),
+            vec!["security", "tls 1.3"]
+        );
+        assert_eq!(
+            parse_keywords(&json!([" privacy policy ", "", "tls 1.3"])).unwrap(),
+            vec!["privacy policy", "tls 1.3"]
+        );
+        assert!(parse_keywords(&json!(["security", 1])).is_err());
+        assert!(parse_keywords(&json!(1)).is_err());
+        assert!(parse_keywords(&Value::Null).unwrap_err().contains("Missing"));
+    }
+
+    #[test]
+    fn mmr_moves_near_duplicates_down() {
+        let candidate = |id: &str, keywords: &[&str], score: f64| {
+            let item: MorselEntry = serde_json::from_value(json!({
+                "id": id,
+                "keywords": keywords,
+                "content": "",
+            }))
+            .unwrap();
+            Match {
+                item,
+                score,
+                query: "security".to_string(),
+                keyword: keywords[0].to_string(),
+                exact: false,
+                explanation: None,
+            }
+        };
+        let candidates = vec![
+            candidate("overview", &["security", "encryption"], 1.0),
+            candidate("duplicate", &["security", "encryption", "tls"], 0.9),
+            candidate("pricing", &["security", "pricing"], 0.8),
+        ];
+        let order = |lambda: f64| {
+            mmr_rerank(candidates.clone(), lambda, MorselSimilarity::Keywords)
+                .into_iter()
+                .map(|m| m.item.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(1.0), vec!["overview", "duplicate", "pricing"]);
+        assert_eq!(order(0.5), vec!["overview", "pricing", "duplicate"]);
+    }
+
+    #[test]
+    fn confidence_bands_and_guidance() {
+        let bands = ConfidenceBands::default();
+        assert_eq!(bands.band(1.0), Confidence::High);
+        assert_eq!(bands.band(0.7), Confidence::High);
+        assert_eq!(bands.band(0.69), Confidence::Medium);
+        assert_eq!(bands.band(0.4), Confidence::Medium);
+        assert_eq!(bands.band(0.39), Confidence::Low);
+
+        let entry = entry("security_overview");
+        let morsels =
+            [0.9, 0.3, 0.2].map(|score| DbMorsel::new(&entry, score).with_confidence(&bands));
+        let guidance = bands.guidance(&morsels);
+        let confidences = guidance.keys().copied().collect::<Vec<_>>();
+        assert_eq!(confidences, vec![Confidence::High, Confidence::Low]);
+        assert_eq!(guidance[&Confidence::Low], bands.low_guidance);
+    }
+
+    #[test]
+    fn see_also_links_are_followed_breadth_first() {
+        let config = load_database();
+        let expanded = |exclude_ids: Value| {
+            let options = options(json!({"exclude_ids": exclude_ids}), false);
+            let mut morsels = vec![DbMorsel {
+                query_keyword: Some("safe".to_string()),
+                ..DbMorsel::new(&entry("security_overview"), 0.9)
+            }];
+            expand_see_also(&mut morsels, &options, config).unwrap();
+            morsels
+        };
+
+        let morsels = expanded(json!([]));
+        let linked = morsels
+            .iter()
+            .map(|morsel| (morsel.id.as_str(), morsel.linked_from.as_deref()))
+            .collect::<Vec<_>>();
+        // the link back to security_overview is skipped, it is in the response already
+        assert_eq!(
+            linked,
+            vec![
+                ("security_overview", None),
+                ("gdpr_compliance", Some("security_overview")),
+                ("data_retention", Some("gdpr_compliance")),
+            ]
+        );
+        assert!(morsels.iter().all(|morsel| morsel.score == 0.9));
+        assert!(morsels.iter().all(|morsel| morsel.query_keyword.as_deref() == Some("safe")));
+
+        // links are not followed through excluded morsels
+        assert_eq!(expanded(json!(["gdpr_compliance"])).len(), 1);
+    }
+}